@@ -0,0 +1,307 @@
+use openssl::memcmp;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use serde_json;
+use server::UserSession;
+use session_store::{self, LockFile};
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use time;
+use uuid::Uuid;
+
+/// Scope of the API requests which a token can make
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Only `GET` and `HEAD` requests
+    ReadOnly,
+    /// Any requests which the user can make
+    ReadWrite,
+}
+
+impl Scope {
+    /// Returns whether the scope allows requests with the HTTP `method`.
+    pub fn allows(&self, method: &str) -> bool {
+        match *self {
+            Scope::ReadOnly => method == "GET" || method == "HEAD",
+            Scope::ReadWrite => true,
+        }
+    }
+}
+
+/// API token kept in the store
+///
+/// Only the SHA-256 hash of the secret is kept, so the token cannot be
+/// shown again after it is created.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Token {
+    pub id: String,
+    /// Name given by the user, such as the script which uses the token
+    pub name: String,
+    pub scope: Scope,
+    /// Hex-encoded SHA-256 hash of the secret
+    hash: String,
+    /// Unix time in seconds when the token was created
+    pub created_at: i64,
+    /// Unix time in seconds after which the token is invalid, which is
+    /// capped by the maximum lifetime of tokens
+    pub expires_at: Option<i64>,
+    /// Session of the user at the latest login, whose roles, proxy users
+    /// and teams are used by the requests with the token
+    pub user_session: UserSession,
+}
+
+/// Store which keeps API tokens in a JSON file
+///
+/// The file is read on every request with a token so that tokens revoked
+/// by other Livy Manager instances sharing it are rejected, and writes are
+/// serialized with a lock file in the same way as `session_store::FileStore`.
+pub struct Store {
+    path: PathBuf,
+    /// Lifetime in seconds after which every token expires
+    max_lifetime_secs: i64,
+    mutex: Mutex<()>,
+}
+
+impl Store {
+    /// Creates a new `Store` which keeps API tokens in `path`.
+    pub fn new(path: &str, max_lifetime_secs: i64) -> Store {
+        Store {
+            path: PathBuf::from(path),
+            max_lifetime_secs,
+            mutex: Mutex::new(()),
+        }
+    }
+
+    /// Returns the unexpired tokens of the user `uid`, oldest first.
+    pub fn list(&self, uid: &str) -> Result<Vec<Token>, String> {
+        let now = now();
+        let mut tokens: Vec<Token> = self.load()?.into_iter()
+            .map(|(_, mut token)| {
+                token.expires_at = Some(self.expires_at(&token));
+                token
+            })
+            .filter(|token| token.user_session.uid == uid && !self.is_expired(token, now))
+            .collect();
+        tokens.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(tokens)
+    }
+
+    /// Creates a new token of `user_session` and returns it with the string
+    /// which is sent as `Authorization: Bearer`.
+    pub fn create(&self, user_session: UserSession, name: &str, scope: Scope, expires_at: Option<i64>) -> Result<(Token, String), String> {
+        let mut secret = vec![0; 32];
+        rand_bytes(&mut secret).map_err(|err| format!("{}", err))?;
+        let secret = hex(&secret);
+
+        let token = Token {
+            id: Uuid::new_v4().simple().to_string(),
+            name: name.to_string(),
+            scope,
+            hash: hex(&sha256(secret.as_bytes())),
+            created_at: now(),
+            expires_at,
+            user_session,
+        };
+        let token = Token { expires_at: Some(self.expires_at(&token)), ..token };
+        let bearer = format!("{}.{}", token.id, secret);
+
+        self.update(|tokens| {
+            // Expired tokens are removed here, where the file is written anyway.
+            let now = now();
+            tokens.retain(|_, token| !self.is_expired(token, now));
+            tokens.insert(token.id.clone(), token.clone());
+        })?;
+
+        Ok((token, bearer))
+    }
+
+    /// Replaces the roles, proxy users and teams of the tokens of the user
+    /// with the ones of `user_session`, with which the user has logged in.
+    pub fn refresh(&self, user_session: &UserSession) -> Result<(), String> {
+        let outdated = |token: &Token| token.user_session.uid == user_session.uid && token.user_session != *user_session;
+
+        if !self.load()?.values().any(&outdated) {
+            return Ok(());
+        }
+
+        self.update(|tokens| {
+            for token in tokens.values_mut().filter(|token| outdated(token)) {
+                token.user_session = user_session.clone();
+            }
+        })
+    }
+
+    /// Removes the token `id` of the user `uid`. Returns `false` if the
+    /// user does not have the token.
+    pub fn revoke(&self, uid: &str, id: &str) -> Result<bool, String> {
+        let mut revoked = false;
+
+        self.update(|tokens| {
+            if tokens.get(id).map_or(false, |token| token.user_session.uid == uid) {
+                tokens.remove(id);
+                revoked = true;
+            }
+        })?;
+
+        Ok(revoked)
+    }
+
+    /// Removes all of the tokens of the user `uid` and returns their number.
+    pub fn revoke_all(&self, uid: &str) -> Result<usize, String> {
+        let mut revoked = 0;
+
+        self.update(|tokens| {
+            let len = tokens.len();
+            tokens.retain(|_, token| token.user_session.uid != uid);
+            revoked = len - tokens.len();
+        })?;
+
+        Ok(revoked)
+    }
+
+    /// Returns the unexpired token whose bearer string is `bearer`.
+    pub fn authenticate(&self, bearer: &str) -> Result<Option<Token>, String> {
+        let mut parts = bearer.splitn(2, '.');
+        let (id, secret) = match (parts.next(), parts.next()) {
+            (Some(id), Some(secret)) => (id, secret),
+            _ => return Ok(None),
+        };
+
+        let hash = hex(&sha256(secret.as_bytes()));
+
+        match self.load()?.remove(id) {
+            Some(token) if memcmp::eq(token.hash.as_bytes(), hash.as_bytes()) && !self.is_expired(&token, now()) => Ok(Some(token)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the Unix time in seconds after which `token` is invalid,
+    /// including the tokens created before the lifetime was capped.
+    fn expires_at(&self, token: &Token) -> i64 {
+        let max = token.created_at + self.max_lifetime_secs;
+        token.expires_at.map_or(max, |expires_at| cmp::min(expires_at, max))
+    }
+
+    fn is_expired(&self, token: &Token, now: i64) -> bool {
+        self.expires_at(token) <= now
+    }
+
+    fn load(&self) -> Result<HashMap<String, Token>, String> {
+        let mut contents = String::new();
+
+        match File::open(&self.path) {
+            Ok(mut f) => f.read_to_string(&mut contents).map_err(|err| format!("{}", err))?,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        serde_json::from_str(&contents).map_err(|err| format!("{}", err))
+    }
+
+    /// Re-reads the file, applies `f` to the tokens and writes them back
+    /// while holding the lock file.
+    fn update<F>(&self, f: F) -> Result<(), String>
+        where F: FnOnce(&mut HashMap<String, Token>) {
+        let _guard = self.mutex.lock().unwrap();
+        let _lock = LockFile::acquire(session_store::with_suffix(&self.path, ".lock"))?;
+
+        let mut tokens = self.load()?;
+        f(&mut tokens);
+
+        let contents = serde_json::to_string(&tokens).map_err(|err| format!("{}", err))?;
+        session_store::write_atomically(&self.path, contents.as_bytes()).map_err(|err| format!("{}", err))
+    }
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use authz::Role;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn user_session(uid: &str, roles: Vec<Role>) -> UserSession {
+        UserSession {
+            uid: uid.to_string(),
+            roles,
+            proxy_users: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_store() {
+        let path = env::temp_dir().join(format!("livy-manager-tokens-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let store = Store::new(path, 24 * 60 * 60);
+        let (token, bearer) = store.create(user_session("alice", vec![Role::User]), "on-call", Scope::ReadOnly, None).unwrap();
+        store.create(user_session("alice", vec![Role::User]), "expired", Scope::ReadWrite, Some(now() - 1)).unwrap();
+        store.create(user_session("bob", vec![Role::User]), "bob's", Scope::ReadWrite, None).unwrap();
+        assert_eq!(Some(token.created_at + 24 * 60 * 60), token.expires_at);
+
+        assert!(!fs::read_to_string(path).unwrap().contains(bearer.split('.').nth(1).unwrap()));
+
+        let found = store.authenticate(&bearer).unwrap().unwrap();
+        assert_eq!(token.id, found.id);
+        assert_eq!("alice", found.user_session.uid);
+        assert!(store.authenticate(&format!("{}.wrong", token.id)).unwrap().is_none());
+        assert!(store.authenticate(&token.id).unwrap().is_none());
+
+        let names: Vec<String> = store.list("alice").unwrap().into_iter().map(|token| token.name).collect();
+        assert_eq!(vec!["on-call".to_string()], names);
+
+        assert_eq!(Ok(false), store.revoke("bob", &token.id));
+        assert_eq!(Ok(true), store.revoke("alice", &token.id));
+        assert!(store.authenticate(&bearer).unwrap().is_none());
+
+        assert_eq!(Ok(1), store.revoke_all("bob"));
+        assert!(store.list("bob").unwrap().is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_refresh() {
+        let path = env::temp_dir().join(format!("livy-manager-tokens-refresh-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let store = Store::new(path, 24 * 60 * 60);
+        let (_, bearer) = store.create(user_session("alice", vec![Role::Admin]), "cleanup", Scope::ReadWrite, None).unwrap();
+        let (_, bob_bearer) = store.create(user_session("bob", vec![Role::Admin]), "bob's", Scope::ReadWrite, None).unwrap();
+
+        // alice is demoted and logs in again.
+        store.refresh(&user_session("alice", vec![Role::User])).unwrap();
+
+        let user_session = store.authenticate(&bearer).unwrap().unwrap().user_session;
+        assert_eq!(vec![Role::User], user_session.roles);
+        assert!(!user_session.is_admin());
+        assert!(store.authenticate(&bob_bearer).unwrap().unwrap().user_session.is_admin());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_scope() {
+        assert!(Scope::ReadOnly.allows("GET"));
+        assert!(!Scope::ReadOnly.allows("DELETE"));
+        assert!(Scope::ReadWrite.allows("POST"));
+    }
+}
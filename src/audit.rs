@@ -0,0 +1,233 @@
+use config;
+use serde_json;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+
+/// Socket of the local syslog daemon
+const SYSLOG_SOCKET: &'static str = "/dev/log";
+/// Priority of the syslog messages, which is the `authpriv` facility and
+/// the `info` severity
+const SYSLOG_PRIORITY: u32 = 10 * 8 + 6;
+
+/// Audited action
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Login,
+    Logout,
+    KillSession,
+    KillBatch,
+    RevokeApiTokens,
+}
+
+/// Outcome of an audited action
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// The user was not allowed to do the action.
+    Denied,
+}
+
+/// Entry of the audit log
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    /// Unix time in seconds
+    pub timestamp: i64,
+    /// Uid of the user, which is unknown for some failed logins
+    pub uid: Option<String>,
+    pub client_ip: String,
+    pub action: Action,
+    pub outcome: Outcome,
+    /// Livy cluster of the target session or batch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// ID of the target session or batch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    /// Login method, the uid whose API tokens were revoked, or the reason
+    /// of the failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Reason given by the user for a kill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Conditions of the events returned by `Log::query`
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub uid: Option<String>,
+    pub action: Option<Action>,
+    /// Unix time in seconds from which the events are returned
+    pub since: Option<i64>,
+    /// Unix time in seconds before which the events are returned
+    pub until: Option<i64>,
+}
+
+impl Filter {
+    fn matches(&self, event: &Event) -> bool {
+        self.uid.as_ref().map_or(true, |uid| event.uid.as_ref() == Some(uid))
+            && self.action.map_or(true, |action| event.action == action)
+            && self.since.map_or(true, |since| event.timestamp >= since)
+            && self.until.map_or(true, |until| event.timestamp < until)
+    }
+}
+
+/// Append-only audit log written to a JSON lines file and to syslog
+pub struct Log {
+    path: Option<String>,
+    syslog: bool,
+    /// Serializes the writes of the threads of the server
+    mutex: Mutex<()>,
+}
+
+impl Log {
+    /// Creates a new `Log`, which discards events if `audit` is not configured.
+    pub fn new(conf: Option<&config::Audit>) -> Log {
+        Log {
+            path: conf.and_then(|conf| conf.path.clone()),
+            syslog: conf.and_then(|conf| conf.syslog).unwrap_or(false),
+            mutex: Mutex::new(()),
+        }
+    }
+
+    /// Returns whether the events can be queried.
+    pub fn is_queryable(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Writes `event`.
+    ///
+    /// Failures are reported to stderr rather than failing the action,
+    /// which has already been done.
+    pub fn write(&self, event: &Event) {
+        let line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(err) => return eprintln!("failed to write the audit log: {}", err),
+        };
+
+        let _guard = self.mutex.lock().unwrap();
+
+        if let Some(ref path) = self.path {
+            // The file is opened on every write so that it can be rotated.
+            let result = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .mode(0o600)
+                .open(path)
+                .and_then(|mut f| f.write_all(format!("{}\n", line).as_bytes()));
+
+            if let Err(err) = result {
+                eprintln!("failed to write the audit log to {}: {}", path, err);
+            }
+        }
+
+        if self.syslog {
+            let message = format!("<{}>livy-manager: {}", SYSLOG_PRIORITY, line);
+            let result = UnixDatagram::unbound()
+                .and_then(|socket| socket.send_to(message.as_bytes(), SYSLOG_SOCKET));
+
+            if let Err(err) = result {
+                eprintln!("failed to write the audit log to syslog: {}", err);
+            }
+        }
+    }
+
+    /// Returns the newest `limit` events which match `filter`, newest first.
+    pub fn query(&self, filter: &Filter, limit: usize) -> Result<Vec<Event>, String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Err("audit.path is not configured".to_string()),
+        };
+
+        let f = match File::open(path) {
+            Ok(f) => f,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Only the newest `limit` events are kept while scanning, so that
+        // a large log does not have to fit in the memory.
+        let mut events = VecDeque::with_capacity(limit);
+        for line in BufReader::new(f).lines() {
+            let line = line.map_err(|err| format!("{}", err))?;
+
+            // A line being written by another thread might be incomplete.
+            if let Ok(event) = serde_json::from_str::<Event>(&line) {
+                if filter.matches(&event) {
+                    if events.len() == limit {
+                        events.pop_front();
+                    }
+                    events.push_back(event);
+                }
+            }
+        }
+
+        Ok(events.into_iter().rev().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn event(timestamp: i64, uid: &str, action: Action) -> Event {
+        Event {
+            timestamp,
+            uid: Some(uid.to_string()),
+            client_ip: "127.0.0.1".to_string(),
+            action,
+            outcome: Outcome::Success,
+            cluster: None,
+            target_id: None,
+            proxy_user: None,
+            app_id: None,
+            detail: None,
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn test_log() {
+        let path = env::temp_dir().join(format!("livy-manager-audit-{}.jsonl", process::id()));
+        let _ = fs::remove_file(&path);
+        let path = path.to_str().unwrap().to_string();
+
+        let log = Log::new(Some(&config::Audit { path: Some(path.clone()), syslog: None }));
+        log.write(&event(100, "alice", Action::Login));
+        log.write(&event(200, "alice", Action::KillSession));
+        log.write(&event(300, "bob", Action::KillSession));
+
+        let timestamps = |filter: &Filter, limit: usize| -> Vec<i64> {
+            log.query(filter, limit).unwrap().iter().map(|event| event.timestamp).collect()
+        };
+
+        assert_eq!(vec![300, 200, 100], timestamps(&Filter::default(), 10));
+        assert_eq!(vec![300], timestamps(&Filter::default(), 1));
+        assert_eq!(vec![300, 200], timestamps(&Filter::default(), 2));
+        assert!(timestamps(&Filter::default(), 0).is_empty());
+        assert_eq!(vec![200, 100], timestamps(&Filter { uid: Some("alice".to_string()), ..Filter::default() }, 10));
+        assert_eq!(vec![300, 200], timestamps(&Filter { action: Some(Action::KillSession), ..Filter::default() }, 10));
+        assert_eq!(vec![200], timestamps(&Filter { since: Some(200), until: Some(300), ..Filter::default() }, 10));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use authz;
+use bcrypt;
+use config::{self, AuthenticatorType, Config, Htpasswd, ProxyUserAlias, LDAP};
+use ldap;
+use server::UserSession;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+
+/// bcrypt hash which is verified against when a user is not in an htpasswd
+/// file, so that unknown users take as long as known ones
+const DUMMY_BCRYPT_HASH: &'static str = "$2b$10$ZWxvwPWrFpCNk6u7ARfe3.SN7GYzZI1czrSi22T2q5qCOmH6QneOy";
+
+/// Error of an authentication
+#[derive(Debug)]
+pub enum AuthError {
+    /// The uid or the password is wrong.
+    InvalidCredentials(String),
+    /// The password could not be verified, such as when the LDAP server
+    /// is down.
+    Unavailable(String),
+}
+
+impl AuthError {
+    /// Returns `Unavailable` with the message of `err`.
+    pub fn unavailable<E: fmt::Display>(err: E) -> AuthError {
+        AuthError::Unavailable(format!("{}", err))
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AuthError::InvalidCredentials(ref msg) | AuthError::Unavailable(ref msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Authenticator of users who log in with a uid and a password
+pub trait Authenticator: Send + Sync {
+    /// Returns the login session of the user if the password is valid.
+    fn authenticate(&self, uid: &str, password: &str) -> Result<UserSession, AuthError>;
+}
+
+/// Creates the chain of the configured authenticators.
+pub fn new(conf: &Config) -> Box<Authenticator> {
+    let teams = conf.teams();
+    let proxy_user_aliases = conf.proxy_user_aliases();
+
+    let authenticators = conf.authenticators().into_iter().map(|authenticator_type| {
+        let authenticator: Box<Authenticator> = match authenticator_type {
+            AuthenticatorType::Ldap => Box::new(LdapAuthenticator {
+                conf: conf.ldap.clone().unwrap(),
+                teams: teams.clone(),
+                proxy_user_aliases: proxy_user_aliases.clone(),
+            }),
+            AuthenticatorType::Htpasswd => Box::new(HtpasswdAuthenticator {
+                conf: conf.htpasswd.clone().unwrap(),
+                teams: teams.clone(),
+                proxy_user_aliases: proxy_user_aliases.clone(),
+            }),
+        };
+        (authenticator, format!("{:?}", authenticator_type).to_lowercase())
+    }).collect();
+
+    Box::new(Chain { authenticators })
+}
+
+/// Authenticator which tries its authenticators in order until one of them
+/// authenticates the user
+pub struct Chain {
+    /// Authenticators and their names for error messages
+    authenticators: Vec<(Box<Authenticator>, String)>,
+}
+
+impl Authenticator for Chain {
+    /// The credentials are invalid if any of the authenticators has rejected
+    /// them, so that an outage of one backend does not exempt guesses
+    /// against the others from the login throttle. The authentication is
+    /// unavailable only if all of them are.
+    fn authenticate(&self, uid: &str, password: &str) -> Result<UserSession, AuthError> {
+        let mut errors = Vec::new();
+        let mut rejected = false;
+
+        for &(ref authenticator, ref name) in &self.authenticators {
+            match authenticator.authenticate(uid, password) {
+                Ok(user_session) => return Ok(user_session),
+                Err(err) => {
+                    rejected |= match err {
+                        AuthError::InvalidCredentials(_) => true,
+                        AuthError::Unavailable(_) => false,
+                    };
+                    errors.push(format!("{}: {}", name, err));
+                },
+            }
+        }
+
+        if rejected {
+            Err(AuthError::InvalidCredentials(errors.join(", ")))
+        } else {
+            Err(AuthError::Unavailable(errors.join(", ")))
+        }
+    }
+}
+
+/// Authenticator which binds to the LDAP server as the user
+pub struct LdapAuthenticator {
+    conf: LDAP,
+    teams: Vec<config::Team>,
+    proxy_user_aliases: Vec<ProxyUserAlias>,
+}
+
+impl Authenticator for LdapAuthenticator {
+    fn authenticate(&self, uid: &str, password: &str) -> Result<UserSession, AuthError> {
+        ldap::auth(&self.conf, &self.teams, &self.proxy_user_aliases, uid, password)
+    }
+}
+
+/// Authenticator which verifies passwords against the bcrypt hashes in an
+/// htpasswd file
+pub struct HtpasswdAuthenticator {
+    conf: Htpasswd,
+    teams: Vec<config::Team>,
+    proxy_user_aliases: Vec<ProxyUserAlias>,
+}
+
+impl Authenticator for HtpasswdAuthenticator {
+    fn authenticate(&self, uid: &str, password: &str) -> Result<UserSession, AuthError> {
+        // The file is read on every login so that users can be added or
+        // removed without restarting Livy Manager.
+        let mut contents = String::new();
+        File::open(&self.conf.path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|err| AuthError::Unavailable(format!("failed to read {}: {}", self.conf.path, err)))?;
+
+        let hash = find_hash(&contents, uid);
+
+        if !verify(password, hash.unwrap_or(DUMMY_BCRYPT_HASH)).map_err(AuthError::unavailable)? || hash.is_none() {
+            return Err(AuthError::InvalidCredentials(format!("invalid uid or password in {}", self.conf.path)));
+        }
+
+        Ok(UserSession {
+            uid: uid.to_string(),
+            roles: self.conf.roles(uid),
+            proxy_users: authz::proxy_users(&self.proxy_user_aliases, uid),
+            teams: authz::static_teams(&self.teams, &self.proxy_user_aliases, uid),
+        })
+    }
+}
+
+/// Returns the hash of `uid` in the contents of an htpasswd file.
+///
+/// Empty lines and lines starting with `#` are ignored.
+fn find_hash<'a>(contents: &'a str, uid: &str) -> Option<&'a str> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.splitn(2, ':');
+            match (fields.next(), fields.next()) {
+                (Some(u), Some(hash)) if u == uid => Some(hash),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Returns whether `password` matches the bcrypt `hash`.
+fn verify(password: &str, hash: &str) -> Result<bool, Box<Error>> {
+    if !(hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$")) {
+        return Err(From::from("only bcrypt hashes are supported"));
+    }
+
+    Ok(bcrypt::verify(password, hash)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &'static str = "$2b$04$nECkqIlZDoCZiEyOXTBDk.zvOvjms1w7wX5eBkXBNbOrAI9NfjX2i";
+
+    /// Authenticator which fails with the error built by its function
+    struct Failing(fn(String) -> AuthError);
+
+    impl Authenticator for Failing {
+        fn authenticate(&self, _: &str, _: &str) -> Result<UserSession, AuthError> {
+            Err((self.0)("failed".to_string()))
+        }
+    }
+
+    fn is_invalid_credentials(errors: Vec<fn(String) -> AuthError>) -> bool {
+        let authenticators = errors.into_iter()
+            .map(|error| (Box::new(Failing(error)) as Box<Authenticator>, "failing".to_string()))
+            .collect();
+
+        match (Chain { authenticators }).authenticate("alice", "secret") {
+            Err(AuthError::InvalidCredentials(_)) => true,
+            Err(AuthError::Unavailable(_)) => false,
+            Ok(_) => panic!("authenticated"),
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        assert!(is_invalid_credentials(vec![AuthError::InvalidCredentials, AuthError::InvalidCredentials]));
+        assert!(is_invalid_credentials(vec![AuthError::Unavailable, AuthError::InvalidCredentials]));
+        assert!(is_invalid_credentials(vec![AuthError::InvalidCredentials, AuthError::Unavailable]));
+        assert!(!is_invalid_credentials(vec![AuthError::Unavailable, AuthError::Unavailable]));
+    }
+
+    #[test]
+    fn test_find_hash() {
+        let contents = format!("# comment\n\nalice:{}\nbob:$apr1$x\n", HASH);
+
+        assert_eq!(Some(HASH), find_hash(&contents, "alice"));
+        assert_eq!(Some("$apr1$x"), find_hash(&contents, "bob"));
+        assert_eq!(None, find_hash(&contents, "carol"));
+        assert_eq!(None, find_hash(&contents, "# comment"));
+    }
+
+    #[test]
+    fn test_verify() {
+        assert!(verify("secret", HASH).unwrap());
+        assert!(!verify("wrong", HASH).unwrap());
+        assert!(verify("secret", &HASH.replace("$2b$", "$2y$")).unwrap());
+        assert!(verify("secret", "$apr1$x").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use config::{self, ProxyUserAlias};
+use regex::Regex;
+use server::UserSession;
+
+/// Role of a user, which is mapped from LDAP groups
+///
+/// A user can have multiple roles and is allowed to perform the actions
+/// which any of them allows.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Can see and operate their own sessions and batches and create new ones
+    User,
+    /// Can see all of the sessions and batches
+    Viewer,
+    /// Can see and kill all of the sessions and batches
+    Operator,
+    /// Can do anything, including creating sessions and batches as other
+    /// users and beyond the resource limits
+    Admin,
+}
+
+/// Action on Livy sessions and batches, or on Livy Manager itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Sees a session or batch, including its log and statements
+    View,
+    /// Kills a session or batch, or cancels a statement
+    Operate,
+    /// Creates a session or batch as the user within the resource limits
+    Create,
+    /// Creates a session or batch as any user beyond the resource limits
+    CreateUnrestricted,
+    /// Queries the audit log or revokes the API tokens of other users
+    Administer,
+}
+
+/// Team of a user, whose members can see each other's sessions and batches
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Team {
+    pub name: String,
+    /// uids of the members, including the user, and their proxy users
+    pub members: Vec<String>,
+    /// Whether the members can also kill each other's sessions and batches
+    pub can_kill: bool,
+}
+
+impl Team {
+    /// Returns true if the team lets its members perform `action` on
+    /// a resource owned by `owner`.
+    fn allows(&self, action: Action, owner: &str) -> bool {
+        let allowed = match action {
+            Action::View => true,
+            Action::Operate => self.can_kill,
+            _ => false,
+        };
+
+        allowed && self.members.iter().any(|member| member == owner)
+    }
+}
+
+impl Role {
+    /// Returns true if the role allows `action` on a resource.
+    /// `is_owner` is whether the resource is owned by the user or,
+    /// as far as `action` is concerned, by their team.
+    fn allows(&self, action: Action, is_owner: bool) -> bool {
+        match (*self, action) {
+            (Role::Admin, _) => true,
+            (Role::Operator, Action::View) | (Role::Operator, Action::Operate) => true,
+            (Role::Viewer, Action::View) => true,
+            (Role::User, Action::View) | (Role::User, Action::Operate) => is_owner,
+            (Role::User, Action::Create) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if the user is allowed to perform `action` on a Livy
+/// session or batch owned by `owner`, which is called only if the
+/// ownership needs to be checked.
+///
+/// Everything is allowed if the authentication is disabled.
+pub fn is_allowed<F>(auth_required: bool, user_session: Option<&UserSession>, action: Action, owner: F) -> bool
+    where F: FnOnce() -> Option<String> {
+    if !auth_required {
+        return true;
+    }
+
+    let user_session = match user_session {
+        Some(user_session) => user_session,
+        None => return false,
+    };
+
+    if user_session.roles.iter().any(|role| role.allows(action, false)) {
+        return true;
+    }
+
+    match owner() {
+        Some(owner) => {
+            let is_owner = is_own(user_session, &owner)
+                || user_session.teams.iter().any(|team| team.allows(action, &owner));
+            user_session.roles.iter().any(|role| role.allows(action, is_owner))
+        },
+        None => false,
+    }
+}
+
+/// Returns true if `owner` of a Livy session or batch is the user or one
+/// of their proxy users, regardless of their teams.
+pub fn is_own(user_session: &UserSession, owner: &str) -> bool {
+    owner == user_session.uid || user_session.proxy_users.iter().any(|proxy_user| proxy_user == owner)
+}
+
+/// Creates the team of `conf` whose members are `members` and their proxy
+/// users by the rules which are not resolved from LDAP.
+pub fn team(conf: &config::Team, members: Vec<String>, proxy_user_aliases: &[ProxyUserAlias]) -> Team {
+    let proxy_users: Vec<String> = members.iter()
+        .flat_map(|member| proxy_users(proxy_user_aliases, member))
+        .collect();
+
+    let mut members = members;
+    members.extend(proxy_users);
+    members.sort();
+    members.dedup();
+
+    Team {
+        name: conf.name.clone(),
+        members,
+        can_kill: conf.can_kill.unwrap_or(false),
+    }
+}
+
+/// Returns the teams in `teams` which `uid` is a member of by their
+/// `members`, ignoring their LDAP groups.
+pub fn static_teams(teams: &[config::Team], proxy_user_aliases: &[ProxyUserAlias], uid: &str) -> Vec<Team> {
+    teams.iter()
+        .filter(|team| team.members.iter().flat_map(|members| members.iter()).any(|member| member == uid))
+        .map(|team| self::team(team, team.members.clone().unwrap_or_default(), proxy_user_aliases))
+        .collect()
+}
+
+/// Returns the proxy users of `uid` by the rules which are not resolved
+/// from LDAP, excluding `uid` itself.
+pub fn proxy_users(aliases: &[ProxyUserAlias], uid: &str) -> Vec<String> {
+    let mut proxy_users = Vec::new();
+
+    for alias in aliases {
+        match *alias {
+            ProxyUserAlias::Rewrite { ref pattern, ref replacement } => {
+                // The pattern has been validated when the configuration was loaded.
+                let pattern = Regex::new(pattern).unwrap();
+                if pattern.is_match(uid) {
+                    proxy_users.push(pattern.replace(uid, replacement.as_str()).into_owned());
+                }
+            },
+            ProxyUserAlias::Static { ref aliases } => {
+                proxy_users.extend(aliases.get(uid).into_iter().flat_map(|aliases| aliases.iter()).cloned());
+            },
+            ProxyUserAlias::LdapAttribute { .. } => (),
+        }
+    }
+
+    proxy_users.retain(|proxy_user| proxy_user != uid);
+    proxy_users.sort();
+    proxy_users.dedup();
+    proxy_users
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_session(roles: Vec<Role>) -> UserSession {
+        UserSession {
+            uid: "alice".to_string(),
+            roles,
+            proxy_users: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let owner = || Some("alice".to_string());
+        let other = || Some("bob".to_string());
+
+        let user = user_session(vec![Role::User]);
+        assert!(is_allowed(true, Some(&user), Action::Operate, owner));
+        assert!(!is_allowed(true, Some(&user), Action::View, other));
+        assert!(!is_allowed(true, Some(&user), Action::CreateUnrestricted, owner));
+
+        let viewer = user_session(vec![Role::Viewer]);
+        assert!(is_allowed(true, Some(&viewer), Action::View, other));
+        assert!(!is_allowed(true, Some(&viewer), Action::Operate, owner));
+        assert!(!is_allowed(true, Some(&viewer), Action::Create, owner));
+
+        let operator = user_session(vec![Role::User, Role::Operator]);
+        assert!(is_allowed(true, Some(&operator), Action::Operate, other));
+        assert!(is_allowed(true, Some(&operator), Action::Create, other));
+        assert!(!is_allowed(true, Some(&operator), Action::CreateUnrestricted, other));
+        assert!(!is_allowed(true, Some(&operator), Action::Administer, || None));
+
+        let admin = user_session(vec![Role::Admin]);
+        assert!(is_allowed(true, Some(&admin), Action::Administer, || None));
+
+        assert!(!is_allowed(true, None, Action::View, owner));
+        assert!(is_allowed(false, None, Action::Operate, other));
+    }
+
+    #[test]
+    fn test_is_allowed_for_team() {
+        let teammate = || Some("bob".to_string());
+        let other = || Some("carol".to_string());
+
+        let mut user = user_session(vec![Role::User]);
+        user.teams.push(Team {
+            name: "data".to_string(),
+            members: vec!["alice".to_string(), "bob".to_string()],
+            can_kill: false,
+        });
+        assert!(is_allowed(true, Some(&user), Action::View, teammate));
+        assert!(!is_allowed(true, Some(&user), Action::Operate, teammate));
+        assert!(!is_allowed(true, Some(&user), Action::View, other));
+
+        user.teams[0].can_kill = true;
+        assert!(is_allowed(true, Some(&user), Action::Operate, teammate));
+        assert!(!is_allowed(true, Some(&user), Action::Operate, other));
+        assert!(!is_own(&user, "bob"));
+
+        let mut viewer = user_session(vec![Role::Viewer]);
+        viewer.teams = user.teams.clone();
+        assert!(!is_allowed(true, Some(&viewer), Action::Operate, teammate));
+    }
+
+    #[test]
+    fn test_is_allowed_for_proxy_user() {
+        let mut user = user_session(vec![Role::User]);
+        user.proxy_users.push("svc_zeppelin_alice".to_string());
+
+        assert!(is_allowed(true, Some(&user), Action::Operate, || Some("svc_zeppelin_alice".to_string())));
+        assert!(!is_allowed(true, Some(&user), Action::View, || Some("svc_zeppelin_bob".to_string())));
+        assert!(is_own(&user, "alice"));
+        assert!(is_own(&user, "svc_zeppelin_alice"));
+    }
+
+    #[test]
+    fn test_proxy_users() {
+        let aliases = vec![
+            ProxyUserAlias::Rewrite {
+                pattern: "^(.+)$".to_string(),
+                replacement: "svc_zeppelin_$1".to_string(),
+            },
+            ProxyUserAlias::Rewrite {
+                pattern: "^(?P<uid>[a-z]+)$".to_string(),
+                replacement: "${uid}@example.com".to_string(),
+            },
+            ProxyUserAlias::Static {
+                aliases: vec![("alice".to_string(), vec!["alice".to_string(), "asmith".to_string()])].into_iter().collect(),
+            },
+            ProxyUserAlias::LdapAttribute {
+                attribute: "mail".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            vec!["alice@example.com", "asmith", "svc_zeppelin_alice"],
+            proxy_users(&aliases, "alice"));
+        assert_eq!(vec!["svc_zeppelin_bob2"], proxy_users(&aliases, "bob2"));
+    }
+}
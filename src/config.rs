@@ -1,14 +1,43 @@
+use authz::Role;
 use iron::typemap::Key;
+use proxy_auth::Cidr;
+use regex::Regex;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use toml;
+use url::Url;
+
+/// Name of the Livy cluster configured by `livy_client`
+pub const DEFAULT_CLUSTER_NAME: &'static str = "default";
+
+/// Path of the endpoint of Livy Manager to which the OpenID provider
+/// redirects users after the authentication
+pub const OIDC_CALLBACK_PATH: &'static str = "/login/oidc/callback";
 
 /// Configuration for Livy Manager
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
+    /// Authenticators which are tried in order when a user logs in
+    pub authenticators: Option<Vec<AuthenticatorType>>,
     pub ldap: Option<LDAP>,
-    pub livy_client: LivyClient,
+    pub htpasswd: Option<Htpasswd>,
+    pub oidc: Option<OIDC>,
+    pub spnego: Option<Spnego>,
+    pub proxy_auth: Option<ProxyAuth>,
+    pub livy_client: Option<LivyClient>,
+    pub livy_clients: Option<Vec<LivyClient>>,
     pub http: HTTP,
+    pub new_session: Option<NewSession>,
+    pub batch_templates: Option<Vec<BatchTemplate>>,
+    pub session_store: Option<SessionStore>,
+    pub session_timeout: Option<SessionTimeout>,
+    pub login_throttle: Option<LoginThrottle>,
+    pub api_tokens: Option<ApiTokens>,
+    pub audit: Option<Audit>,
+    pub kill_notification: Option<KillNotification>,
+    pub teams: Option<Vec<Team>>,
+    pub proxy_user_aliases: Option<Vec<ProxyUserAlias>>,
 }
 
 impl Config {
@@ -17,7 +46,294 @@ impl Config {
         let mut f = File::open(conf_path).unwrap();
         let mut contents = String::new();
         f.read_to_string(&mut contents).unwrap();
-        toml::from_str(contents.as_str()).unwrap()
+        let conf: Config = toml::from_str(contents.as_str()).unwrap();
+        if let Err(err) = conf.validate() {
+            panic!("invalid configuration: {}", err);
+        }
+        conf
+    }
+
+    /// Returns the configured Livy clusters.
+    ///
+    /// `livy_clients` takes precedence over `livy_client`.
+    pub fn clusters(&self) -> Vec<LivyClient> {
+        match (self.livy_clients.as_ref(), self.livy_client.as_ref()) {
+            (Some(livy_clients), _) => livy_clients.clone(),
+            (None, Some(livy_client)) => vec![livy_client.clone()],
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// Returns the Livy cluster whose name is `name`.
+    pub fn cluster(&self, name: &str) -> Option<LivyClient> {
+        self.clusters().into_iter().find(|cluster| cluster.name() == name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let clusters = self.clusters();
+
+        if clusters.is_empty() {
+            return Err("either livy_client or livy_clients is required".to_string());
+        }
+
+        for (i, cluster) in clusters.iter().enumerate() {
+            if self.livy_clients.is_some() && cluster.name.is_none() {
+                return Err(format!("name is required for livy_clients: {}", cluster.url));
+            }
+
+            if clusters[..i].iter().any(|c| c.name() == cluster.name()) {
+                return Err(format!("duplicate Livy cluster name: {}", cluster.name()));
+            }
+        }
+
+        let authenticators = self.authenticators();
+
+        for (i, authenticator) in authenticators.iter().enumerate() {
+            if authenticators[..i].contains(authenticator) {
+                return Err(format!("duplicate authenticator: {:?}", authenticator));
+            }
+
+            match *authenticator {
+                AuthenticatorType::Ldap if self.ldap.is_none() => {
+                    return Err("ldap is required for the ldap authenticator".to_string());
+                },
+                AuthenticatorType::Htpasswd if self.htpasswd.is_none() => {
+                    return Err("htpasswd is required for the htpasswd authenticator".to_string());
+                },
+                _ => (),
+            }
+        }
+
+        if let Some(ref oidc) = self.oidc {
+            oidc.validate()?;
+
+            if self.http.cookie().same_site.as_ref().map(String::as_str) == Some("Strict") {
+                return Err("oidc requires http.cookie.same_site = \"Lax\" or \"None\" because \
+                            the login session cookie has to be sent after the redirection \
+                            from the OpenID provider".to_string());
+            }
+        }
+
+        if let Some(ref spnego) = self.spnego {
+            if !cfg!(feature = "spnego") {
+                return Err("spnego requires Livy Manager built with the spnego feature".to_string());
+            }
+
+            if spnego.realms.is_empty() {
+                return Err("spnego.realms must not be empty".to_string());
+            }
+        }
+
+        if let Some(ref proxy_auth) = self.proxy_auth {
+            if proxy_auth.trusted_proxies.is_empty() {
+                return Err("proxy_auth.trusted_proxies must not be empty".to_string());
+            }
+
+            for proxy in &proxy_auth.trusted_proxies {
+                Cidr::parse(proxy).map_err(|err| format!("invalid proxy_auth.trusted_proxies: {}", err))?;
+            }
+        }
+
+        if let Some(ref ldap) = self.ldap {
+            match (ldap.user_dn.as_ref(), ldap.user_search.as_ref()) {
+                (Some(_), None) => (),
+                (None, Some(user_search)) if user_search.filter.contains("{}") => (),
+                (None, Some(user_search)) => {
+                    return Err(format!("ldap.user_search.filter must contain {{}}: {}", user_search.filter));
+                },
+                _ => return Err("either ldap.user_dn or ldap.user_search is required".to_string()),
+            }
+
+            if ldap.starttls.unwrap_or(false) && !ldap.url.to_lowercase().starts_with("ldap://") {
+                return Err(format!("ldap.starttls is used only with ldap://: {}", ldap.url));
+            }
+
+            if ldap.require_tls.unwrap_or(false) && !ldap.is_tls() {
+                return Err(format!("ldap.require_tls requires ldaps:// or ldap.starttls = true: {}", ldap.url));
+            }
+
+            if ldap.ca_file.is_some() && !ldap.is_tls() {
+                return Err(format!("ldap.ca_file is used only with ldaps:// or ldap.starttls = true: {}", ldap.url));
+            }
+        }
+
+        if let Some(ref throttle) = self.login_throttle {
+            let values = [
+                ("max_failures", throttle.max_failures.map(i64::from)),
+                ("max_failures_per_ip", throttle.max_failures_per_ip.map(i64::from)),
+                ("backoff_base_secs", throttle.backoff_base_secs),
+                ("lockout_secs", throttle.lockout_secs),
+            ];
+
+            for &(name, value) in values.iter() {
+                if let Some(value) = value {
+                    if value <= 0 {
+                        return Err(format!("login_throttle.{} must be positive", name));
+                    }
+                }
+            }
+
+            for proxy in throttle.trusted_proxies.iter().flat_map(|proxies| proxies.iter()) {
+                Cidr::parse(proxy).map_err(|err| format!("invalid login_throttle.trusted_proxies: {}", err))?;
+            }
+        }
+
+        if let Some(ref audit) = self.audit {
+            if audit.path.is_none() && !audit.syslog.unwrap_or(false) {
+                return Err("either audit.path or audit.syslog = true is required".to_string());
+            }
+        }
+
+        // The notifications refer to the reasons kept in the audit log.
+        if self.kill_notification.is_some() && self.audit_path().is_none() {
+            return Err("kill_notification requires audit.path, in which the reasons of kills are kept".to_string());
+        }
+
+        if let Some(ref kill_notification) = self.kill_notification {
+            match kill_notification.channel {
+                NotificationChannel::Webhook { ref url, .. } => {
+                    if !url.starts_with("https://") && !url.starts_with("http://") {
+                        return Err(format!("kill_notification.channel.url must be an http(s):// URL: {}", url));
+                    }
+                },
+                NotificationChannel::Smtp { ref url, .. } => {
+                    if !url.starts_with("smtps://") && !url.starts_with("smtp://") {
+                        return Err(format!("kill_notification.channel.url must be an smtp(s):// URL: {}", url));
+                    }
+                },
+            }
+        }
+
+        if let Some(ref api_tokens) = self.api_tokens {
+            if !self.auth_required() {
+                return Err("api_tokens requires an authentication".to_string());
+            }
+
+            if api_tokens.max_lifetime_days() <= 0 {
+                return Err("api_tokens.max_lifetime_days must be positive".to_string());
+            }
+        }
+
+        for (i, team) in self.teams().iter().enumerate() {
+            if self.teams()[..i].iter().any(|t| t.name == team.name) {
+                return Err(format!("duplicate team name: {}", team.name));
+            }
+
+            if team.members.is_none() && team.group_dns.is_none() {
+                return Err(format!("either members or group_dns is required for team {}", team.name));
+            }
+
+            if team.group_dns.is_some() && self.ldap.is_none() {
+                return Err(format!("group_dns of team {} requires ldap", team.name));
+            }
+        }
+
+        for alias in self.proxy_user_aliases() {
+            match alias {
+                ProxyUserAlias::Rewrite { ref pattern, .. } => {
+                    if let Err(err) = Regex::new(pattern) {
+                        return Err(format!("invalid proxy_user_aliases.pattern: {}", err));
+                    }
+                },
+                ProxyUserAlias::LdapAttribute { ref attribute } if self.ldap.is_none() => {
+                    return Err(format!("proxy_user_aliases from the LDAP attribute {} requires ldap", attribute));
+                },
+                _ => (),
+            }
+        }
+
+        let cookie = self.http.cookie();
+
+        match cookie.same_site.as_ref().map(String::as_str) {
+            Some("None") if !cookie.secure.unwrap_or(false) => {
+                return Err("http.cookie.same_site = \"None\" requires http.cookie.secure = true".to_string());
+            },
+            Some("Strict") | Some("Lax") | Some("None") | None => (),
+            Some(same_site) => return Err(format!("invalid http.cookie.same_site: {}", same_site)),
+        }
+
+        if let Some(ref timeout) = self.session_timeout {
+            let values = [
+                ("absolute_secs", timeout.absolute_secs),
+                ("idle_secs", timeout.idle_secs),
+                ("sweep_interval_secs", timeout.sweep_interval_secs),
+            ];
+
+            for &(name, value) in values.iter() {
+                if let Some(value) = value {
+                    if value <= 0 {
+                        return Err(format!("session_timeout.{} must be positive", name));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the authenticators in the order in which they are tried.
+    ///
+    /// If `authenticators` is not set, LDAP and then htpasswd are used
+    /// if they are configured. No authenticators disable the authentication.
+    pub fn authenticators(&self) -> Vec<AuthenticatorType> {
+        if let Some(ref authenticators) = self.authenticators {
+            return authenticators.clone();
+        }
+
+        let mut authenticators = Vec::new();
+        if self.ldap.is_some() {
+            authenticators.push(AuthenticatorType::Ldap);
+        }
+        if self.htpasswd.is_some() {
+            authenticators.push(AuthenticatorType::Htpasswd);
+        }
+        authenticators
+    }
+
+    /// Returns whether users have to log in.
+    pub fn auth_required(&self) -> bool {
+        self.login_enabled() || self.proxy_auth.is_some()
+    }
+
+    /// Returns whether users can log in on the login page of Livy Manager,
+    /// rather than only through the authenticating reverse proxy.
+    pub fn login_enabled(&self) -> bool {
+        !self.authenticators().is_empty() || self.oidc.is_some() || self.spnego.is_some()
+    }
+
+    /// Returns the file of the audit log, if it is configured.
+    pub fn audit_path(&self) -> Option<&str> {
+        self.audit.as_ref().and_then(|audit| audit.path.as_ref()).map(String::as_str)
+    }
+
+    /// Returns the configured teams.
+    pub fn teams(&self) -> Vec<Team> {
+        self.teams.clone().unwrap_or_default()
+    }
+
+    /// Returns the rules which map uids to proxy users.
+    pub fn proxy_user_aliases(&self) -> Vec<ProxyUserAlias> {
+        self.proxy_user_aliases.clone().unwrap_or_default()
+    }
+
+    /// Returns the limits of failed logins.
+    pub fn login_throttle(&self) -> LoginThrottle {
+        self.login_throttle.clone().unwrap_or(LoginThrottle {
+            max_failures: None,
+            max_failures_per_ip: None,
+            backoff_base_secs: None,
+            lockout_secs: None,
+            trusted_proxies: None,
+        })
+    }
+
+    /// Returns the timeouts of login sessions.
+    pub fn session_timeout(&self) -> SessionTimeout {
+        self.session_timeout.clone().unwrap_or(SessionTimeout {
+            absolute_secs: None,
+            idle_secs: None,
+            sweep_interval_secs: None,
+        })
     }
 }
 
@@ -25,25 +341,712 @@ impl Key for Config {
     type Value = Self;
 }
 
+/// Type of an authenticator of users
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthenticatorType {
+    Ldap,
+    Htpasswd,
+}
+
+/// Configuration for the authentication with an htpasswd file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Htpasswd {
+    /// File whose lines are `uid:bcrypt hash`, which is read on every login
+    pub path: String,
+    /// Roles granted to the users in the file, which defaults to `["user"]`
+    pub default_roles: Option<Vec<Role>>,
+    /// Roles granted to the listed users
+    pub roles: Option<Vec<UserRole>>,
+}
+
+impl Htpasswd {
+    /// Returns the roles of the user `uid`.
+    pub fn roles(&self, uid: &str) -> Vec<Role> {
+        user_roles(self.default_roles.as_ref(), self.roles.as_ref(), uid)
+    }
+}
+
+/// Configuration for the SPNEGO single sign-on, with which browsers log
+/// in with the Kerberos tickets of their users
+#[derive(Clone, Debug, Deserialize)]
+pub struct Spnego {
+    /// Keytab which contains the keys of the `HTTP` service principal of
+    /// Livy Manager
+    pub keytab: String,
+    /// Realms whose users are accepted. The uid of a user is the principal
+    /// without the realm.
+    pub realms: Vec<String>,
+    /// Roles granted to every authenticated user, which defaults to `["user"]`
+    pub default_roles: Option<Vec<Role>>,
+    /// Roles granted to the listed users
+    pub roles: Option<Vec<UserRole>>,
+}
+
+impl Spnego {
+    /// Returns the roles of the user `uid`.
+    pub fn roles(&self, uid: &str) -> Vec<Role> {
+        user_roles(self.default_roles.as_ref(), self.roles.as_ref(), uid)
+    }
+}
+
+/// Role granted to the listed users
+#[derive(Clone, Debug, Deserialize)]
+pub struct UserRole {
+    pub role: Role,
+    pub uids: Vec<String>,
+}
+
+/// Returns the roles of the user `uid`, which are `default_roles` (`["user"]`
+/// by default) and the ones of `roles` which list `uid`.
+fn user_roles(default_roles: Option<&Vec<Role>>, roles: Option<&Vec<UserRole>>, uid: &str) -> Vec<Role> {
+    let mut user_roles = default_roles.cloned().unwrap_or(vec![Role::User]);
+
+    for role in roles.iter().flat_map(|roles| roles.iter()) {
+        if role.uids.iter().any(|u| u == uid) && !user_roles.contains(&role.role) {
+            user_roles.push(role.role);
+        }
+    }
+
+    user_roles
+}
+
+/// Configuration for the authentication by a reverse proxy which sets the
+/// uid and the groups of the user in the headers of every request
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProxyAuth {
+    /// IP addresses or CIDRs of the proxies whose headers are trusted
+    pub trusted_proxies: Vec<String>,
+    /// Header whose value is the uid, which defaults to `X-Forwarded-User`
+    pub user_header: Option<String>,
+    /// Header whose comma-separated values are the groups of the user,
+    /// which defaults to `X-Forwarded-Groups`
+    pub groups_header: Option<String>,
+    /// Group whose members are admin users
+    pub admin_group: Option<String>,
+    /// Roles granted to every authenticated user, which defaults to `["user"]`
+    pub default_roles: Option<Vec<Role>>,
+    /// URL to which users are redirected on logout, such as the sign-out
+    /// endpoint of the proxy
+    pub logout_url: Option<String>,
+}
+
+impl ProxyAuth {
+    /// Returns the header whose value is the uid.
+    pub fn user_header(&self) -> String {
+        self.user_header.clone().unwrap_or_else(|| "X-Forwarded-User".to_string())
+    }
+
+    /// Returns the header whose values are the groups of the user.
+    pub fn groups_header(&self) -> String {
+        self.groups_header.clone().unwrap_or_else(|| "X-Forwarded-Groups".to_string())
+    }
+
+    /// Returns the roles of the member of `groups`.
+    pub fn roles(&self, groups: &[String]) -> Vec<Role> {
+        let mut roles = self.default_roles.clone().unwrap_or(vec![Role::User]);
+
+        if let Some(ref admin_group) = self.admin_group {
+            if groups.contains(admin_group) && !roles.contains(&Role::Admin) {
+                roles.push(Role::Admin);
+            }
+        }
+
+        roles
+    }
+}
+
+/// Configuration for the OpenID Connect single sign-on
+#[derive(Clone, Debug, Deserialize)]
+pub struct OIDC {
+    /// Issuer whose discovery document is at
+    /// `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// URL of `/login/oidc/callback` of Livy Manager, which is registered
+    /// with the OpenID provider
+    pub redirect_uri: String,
+    /// Scopes requested in addition to `openid`, which default to `["profile"]`
+    pub scopes: Option<Vec<String>>,
+    /// Claim of the ID token whose value is the uid, which defaults to
+    /// `preferred_username`
+    pub uid_claim: Option<String>,
+    /// Claim of the ID token whose values are the groups of the user,
+    /// which defaults to `groups`
+    pub groups_claim: Option<String>,
+    /// Roles granted to every authenticated user, which defaults to `["user"]`
+    pub default_roles: Option<Vec<Role>>,
+    /// Roles granted to the members of the groups in the groups claim
+    pub roles: Option<Vec<OidcRole>>,
+    /// PEM file of the CA certificates which the certificate of the OpenID
+    /// provider is verified against, instead of the system ones
+    pub ca_file: Option<String>,
+}
+
+impl OIDC {
+    fn validate(&self) -> Result<(), String> {
+        let issuer = Url::parse(&self.issuer)
+            .map_err(|err| format!("invalid oidc.issuer: {}: {}", self.issuer, err))?;
+
+        // Plain HTTP is allowed only for a local provider such as a mock for tests.
+        let is_loopback = match issuer.host_str() {
+            Some("localhost") | Some("127.0.0.1") | Some("[::1]") => true,
+            _ => false,
+        };
+
+        if issuer.scheme() != "https" && !(issuer.scheme() == "http" && is_loopback) {
+            return Err(format!("oidc.issuer must use https://: {}", self.issuer));
+        }
+
+        let redirect_uri = Url::parse(&self.redirect_uri)
+            .map_err(|err| format!("invalid oidc.redirect_uri: {}: {}", self.redirect_uri, err))?;
+
+        if redirect_uri.path() != OIDC_CALLBACK_PATH {
+            return Err(format!("the path of oidc.redirect_uri must be {}: {}", OIDC_CALLBACK_PATH, self.redirect_uri));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the requested scopes, which always include `openid`.
+    pub fn scopes(&self) -> Vec<String> {
+        let mut scopes = vec!["openid".to_string()];
+        for scope in self.scopes.clone().unwrap_or(vec!["profile".to_string()]) {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        scopes
+    }
+
+    /// Returns the claim whose value is the uid.
+    pub fn uid_claim(&self) -> String {
+        self.uid_claim.clone().unwrap_or_else(|| "preferred_username".to_string())
+    }
+
+    /// Returns the claim whose values are the groups of the user.
+    pub fn groups_claim(&self) -> String {
+        self.groups_claim.clone().unwrap_or_else(|| "groups".to_string())
+    }
+
+    /// Returns the roles of the member of `groups`.
+    pub fn roles(&self, groups: &[String]) -> Vec<Role> {
+        let mut roles = self.default_roles.clone().unwrap_or(vec![Role::User]);
+
+        for role in self.roles.iter().flat_map(|roles| roles.iter()) {
+            if role.groups.iter().any(|group| groups.contains(group)) && !roles.contains(&role.role) {
+                roles.push(role.role);
+            }
+        }
+
+        roles
+    }
+}
+
+/// Role granted to the members of any of the groups in the groups claim
+#[derive(Clone, Debug, Deserialize)]
+pub struct OidcRole {
+    pub role: Role,
+    pub groups: Vec<String>,
+}
+
 /// Configuration for the LDAP authentication
 #[derive(Clone, Debug, Deserialize)]
 pub struct LDAP {
     pub url: String,
-    pub user_dn: String,
-    pub admin_group_dn: String,
+    /// Template of the DN of a user in which `{}` is replaced with the uid
+    pub user_dn: Option<String>,
+    /// Search for the DN of a user, which is used instead of `user_dn`
+    pub user_search: Option<LdapUserSearch>,
+    pub admin_group_dn: Option<String>,
+    /// DNs of the groups whose members are admin users, in addition to `admin_group_dn`
+    pub admin_group_dns: Option<Vec<String>>,
+    /// How to determine whether a user is a member of a group
+    pub group_membership: Option<GroupMembership>,
+    /// Attribute of user entries whose value is the uid, which defaults to `uid`
+    pub uid_attribute: Option<String>,
+    /// Roles granted to the members of LDAP groups
+    pub roles: Option<Vec<LdapRole>>,
+    /// Roles granted to every authenticated user, which defaults to `["user"]`
+    pub default_roles: Option<Vec<Role>>,
+    /// PEM file of the CA certificates which the certificate of the LDAP
+    /// server is verified against, in addition to the system ones
+    pub ca_file: Option<String>,
+    /// Upgrades the connection to an ldap:// URL with StartTLS if `true`
+    pub starttls: Option<bool>,
+    /// Refuses to bind to the LDAP server without TLS if `true`
+    pub require_tls: Option<bool>,
+}
+
+impl LDAP {
+    /// Returns whether the connection to the LDAP server is encrypted.
+    pub fn is_tls(&self) -> bool {
+        self.url.to_lowercase().starts_with("ldaps://") || self.starttls.unwrap_or(false)
+    }
+
+    /// Returns the DNs of the groups whose members are admin users.
+    pub fn admin_group_dns(&self) -> Vec<String> {
+        self.admin_group_dn.iter()
+            .chain(self.admin_group_dns.iter().flat_map(|dns| dns.iter()))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the roles granted to every authenticated user.
+    pub fn default_roles(&self) -> Vec<Role> {
+        self.default_roles.clone().unwrap_or(vec![Role::User])
+    }
+
+    /// Returns the attribute of user entries whose value is the uid.
+    pub fn uid_attribute(&self) -> String {
+        self.uid_attribute.clone().unwrap_or_else(|| "uid".to_string())
+    }
+
+    /// Returns the DN under which users are searched for, which is either
+    /// `user_search.base_dn` or the parent of `user_dn`.
+    pub fn user_base_dn(&self) -> Option<String> {
+        match (self.user_search.as_ref(), self.user_dn.as_ref()) {
+            (Some(user_search), _) => Some(user_search.base_dn.clone()),
+            (None, Some(user_dn)) => user_dn.splitn(2, ',').nth(1).map(|dn| dn.trim().to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns how to determine the group membership, which defaults to
+    /// searching groups for the `member` attribute.
+    pub fn group_membership(&self) -> GroupMembership {
+        self.group_membership.clone().unwrap_or(GroupMembership::MemberAttribute {
+            attribute: None,
+            nested_base_dn: None,
+        })
+    }
+}
+
+/// Role granted to the members of any of the LDAP groups
+#[derive(Clone, Debug, Deserialize)]
+pub struct LdapRole {
+    pub role: Role,
+    pub group_dns: Vec<String>,
+}
+
+/// Strategy for determining whether a user is a member of a group
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GroupMembership {
+    /// Groups have an attribute whose values are their members, such as
+    /// `member` (default), `uniqueMember` or `memberUid`. Values of `memberUid`
+    /// are uids and the others are DNs. If `nested_base_dn` is set, groups under
+    /// it which are members of a group are followed as nested groups.
+    MemberAttribute {
+        attribute: Option<String>,
+        nested_base_dn: Option<String>,
+    },
+    /// Users have an attribute whose values are the DNs of their groups,
+    /// such as `memberOf` (default).
+    MemberOf {
+        attribute: Option<String>,
+    },
+    /// Active Directory `LDAP_MATCHING_RULE_IN_CHAIN`, which follows nested groups.
+    InChain,
+}
+
+/// Team whose members can see each other's Livy sessions and batches
+#[derive(Clone, Debug, Deserialize)]
+pub struct Team {
+    pub name: String,
+    /// uids of the members
+    pub members: Option<Vec<String>>,
+    /// DNs of the LDAP groups whose members are also members of the team
+    pub group_dns: Option<Vec<String>>,
+    /// Whether the members can also kill each other's sessions and batches
+    pub can_kill: Option<bool>,
+}
+
+/// Rule which maps the uid of a user to the proxy users of the Livy
+/// sessions and batches owned by the user
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProxyUserAlias {
+    /// Rewrites the uid matching `pattern` with `replacement`, in which
+    /// `$1` and `${name}` are replaced with the captured groups.
+    Rewrite {
+        pattern: String,
+        replacement: String,
+    },
+    /// Values of the attribute of the LDAP entry of the user, such as `mail`
+    LdapAttribute {
+        attribute: String,
+    },
+    /// Table from uids to their proxy users
+    Static {
+        aliases: HashMap<String, Vec<String>>,
+    },
+}
+
+/// Configuration for searching for the DN of a user with a service account
+#[derive(Clone, Debug, Deserialize)]
+pub struct LdapUserSearch {
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub base_dn: String,
+    /// Search filter in which `{}` is replaced with the uid,
+    /// such as `(sAMAccountName={})`
+    pub filter: String,
 }
 
 /// Configuration for the Livy client
 #[derive(Clone, Debug, Deserialize)]
 pub struct LivyClient {
+    pub name: Option<String>,
     pub url: String,
     pub gssnegotiate: Option<bool>,
     pub username: Option<String>,
 }
 
+impl LivyClient {
+    /// Returns the name of the Livy cluster.
+    pub fn name(&self) -> &str {
+        match self.name {
+            Some(ref name) => name.as_str(),
+            None => DEFAULT_CLUSTER_NAME,
+        }
+    }
+}
+
 /// Configuration for HTTP
 #[derive(Clone, Debug, Deserialize)]
 pub struct HTTP {
     pub addr: String,
     pub num_threads: usize,
+    pub cookie: Option<Cookie>,
+    pub tls: Option<TLS>,
+}
+
+impl HTTP {
+    /// Returns the attributes of the cookies set by Livy Manager.
+    ///
+    /// `secure` defaults to whether HTTPS is served.
+    pub fn cookie(&self) -> Cookie {
+        let mut cookie = self.cookie.clone().unwrap_or(Cookie {
+            secure: None,
+            http_only: None,
+            same_site: None,
+        });
+
+        if cookie.secure.is_none() {
+            cookie.secure = Some(self.tls.is_some());
+        }
+
+        cookie
+    }
+}
+
+/// Configuration for serving HTTPS
+#[derive(Clone, Debug, Deserialize)]
+pub struct TLS {
+    /// PEM file of the server certificate followed by the intermediate certificates
+    pub cert_chain_file: String,
+    /// PEM file of the private key of the server certificate
+    pub private_key_file: String,
+    /// PEM file of the CA certificates which client certificates are verified
+    /// against. Client certificates are required if this is set.
+    pub client_ca_file: Option<String>,
+}
+
+/// Attributes of the cookies set by Livy Manager
+#[derive(Clone, Debug, Deserialize)]
+pub struct Cookie {
+    pub secure: Option<bool>,
+    pub http_only: Option<bool>,
+    pub same_site: Option<String>,
+}
+
+impl Cookie {
+    /// Returns the attributes appended to the `Set-Cookie` headers.
+    ///
+    /// `HttpOnly` and `SameSite=Lax` are set by default.
+    pub fn attributes(&self) -> String {
+        let mut attributes = String::new();
+
+        if self.secure.unwrap_or(false) {
+            attributes.push_str("; Secure");
+        }
+
+        if self.http_only.unwrap_or(true) {
+            attributes.push_str("; HttpOnly");
+        }
+
+        attributes.push_str("; SameSite=");
+        attributes.push_str(self.same_site.as_ref().map(String::as_str).unwrap_or("Lax"));
+
+        attributes
+    }
+}
+
+/// Configuration for the store of login sessions
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionStore {
+    /// Keeps login sessions in memory
+    Memory,
+    /// Keeps login sessions in a JSON file on `path`
+    File { path: String },
+}
+
+/// Configuration for the audit log of logins and kills
+#[derive(Clone, Debug, Deserialize)]
+pub struct Audit {
+    /// JSON lines file to which the events are appended, which is also
+    /// read by `/api/audit`
+    pub path: Option<String>,
+    /// Whether the events are also sent to the local syslog daemon
+    pub syslog: Option<bool>,
+}
+
+/// Configuration for the notifications to the proxy users of the sessions
+/// and batches killed by other users
+#[derive(Clone, Debug, Deserialize)]
+pub struct KillNotification {
+    pub channel: NotificationChannel,
+    /// Template of the subject, in which `{name}` is replaced with the
+    /// field `name` of `kill_notification::Kill`
+    pub subject: Option<String>,
+    /// Template of the message, as is `subject`
+    pub message: Option<String>,
+}
+
+/// Channel through which kill notifications are delivered
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// POSTs the notification as JSON to `url`
+    Webhook {
+        url: String,
+        ca_file: Option<String>,
+    },
+    /// Mails the notification through the SMTP server on `url`
+    Smtp {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+        /// `From` address of the mails
+        from: String,
+        /// Template of the recipient address, such as `{proxy_user}@example.com`
+        to: String,
+        ca_file: Option<String>,
+        /// Whether STARTTLS is required on smtp://, which defaults to true
+        require_tls: Option<bool>,
+    },
+}
+
+/// Limits of failed logins with the login form
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoginThrottle {
+    /// Failures of a uid after which it is locked out
+    pub max_failures: Option<u32>,
+    /// Failures from an IP address after which it is locked out
+    pub max_failures_per_ip: Option<u32>,
+    /// Seconds for which logins are rejected after the first failure,
+    /// which double on every further failure
+    pub backoff_base_secs: Option<i64>,
+    /// Seconds for which a uid or an IP address is locked out, after which
+    /// its failures are forgotten
+    pub lockout_secs: Option<i64>,
+    /// IP addresses or CIDRs of the reverse proxies whose `X-Forwarded-For`
+    /// headers are trusted
+    pub trusted_proxies: Option<Vec<String>>,
+}
+
+impl LoginThrottle {
+    /// Returns the failures of a uid before the lockout, which defaults to 5.
+    pub fn max_failures(&self) -> u32 {
+        self.max_failures.unwrap_or(5)
+    }
+
+    /// Returns the failures from an IP address before the lockout, which
+    /// defaults to 20.
+    pub fn max_failures_per_ip(&self) -> u32 {
+        self.max_failures_per_ip.unwrap_or(20)
+    }
+
+    /// Returns the base of the backoff, which defaults to 1 second.
+    pub fn backoff_base_secs(&self) -> i64 {
+        self.backoff_base_secs.unwrap_or(1)
+    }
+
+    /// Returns the duration of the lockout, which defaults to 15 minutes.
+    pub fn lockout_secs(&self) -> i64 {
+        self.lockout_secs.unwrap_or(15 * 60)
+    }
+}
+
+/// Configuration for the personal API tokens of users
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiTokens {
+    /// JSON file which keeps the hashes of the tokens
+    pub path: String,
+    pub max_lifetime_days: Option<i64>,
+}
+
+impl ApiTokens {
+    /// Returns the lifetime in days after which every token expires,
+    /// which defaults to 90 days.
+    pub fn max_lifetime_days(&self) -> i64 {
+        self.max_lifetime_days.unwrap_or(90)
+    }
+}
+
+/// Timeouts of login sessions in seconds
+#[derive(Clone, Debug, Deserialize)]
+pub struct SessionTimeout {
+    pub absolute_secs: Option<i64>,
+    pub idle_secs: Option<i64>,
+    pub sweep_interval_secs: Option<i64>,
+}
+
+impl SessionTimeout {
+    /// Returns the lifetime of a login session, which defaults to 7 days.
+    pub fn absolute_secs(&self) -> i64 {
+        self.absolute_secs.unwrap_or(7 * 24 * 60 * 60)
+    }
+
+    /// Returns the interval of evicting expired login sessions,
+    /// which defaults to 60 seconds.
+    pub fn sweep_interval_secs(&self) -> i64 {
+        self.sweep_interval_secs.unwrap_or(60)
+    }
+}
+
+/// Configuration for creating new interactive sessions from the web UI
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewSession {
+    pub presets: Option<Vec<SessionPreset>>,
+    pub limits: Option<ResourceLimits>,
+}
+
+/// Preset values of the new session form
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionPreset {
+    pub name: String,
+    pub kind: Option<String>,
+    pub driver_memory: Option<String>,
+    pub driver_cores: Option<i64>,
+    pub executor_memory: Option<String>,
+    pub executor_cores: Option<i64>,
+    pub num_executors: Option<i64>,
+    pub queue: Option<String>,
+    pub jars: Option<Vec<String>>,
+    pub conf: Option<HashMap<String, String>>,
+}
+
+/// Upper limits of the resources which non-admin users can request
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResourceLimits {
+    pub driver_memory: Option<String>,
+    pub driver_cores: Option<i64>,
+    pub executor_memory: Option<String>,
+    pub executor_cores: Option<i64>,
+    pub num_executors: Option<i64>,
+    pub queues: Option<Vec<String>>,
+    /// Spark properties which non-admin users can set in `conf`, which are
+    /// keys or prefixes ending with `*`
+    pub allowed_conf_keys: Option<Vec<String>>,
+}
+
+impl ResourceLimits {
+    /// Returns true if non-admin users can set the Spark property `key`,
+    /// which defaults to the SQL properties and the serializer.
+    pub fn allows_conf_key(&self, key: &str) -> bool {
+        let default = || vec![
+            "spark.sql.*".to_string(),
+            "spark.serializer".to_string(),
+            "spark.kryoserializer.buffer.max".to_string(),
+        ];
+
+        self.allowed_conf_keys.clone().unwrap_or_else(default).iter().any(|allowed| {
+            if allowed.ends_with('*') {
+                key.starts_with(&allowed[..allowed.len() - 1])
+            } else {
+                key == allowed
+            }
+        })
+    }
+}
+
+/// Batch job template which users can submit from the web UI
+///
+/// `{name}` in `file`, `class_name`, `args`, `py_files`, `name` and the
+/// values of `conf` is replaced with the value of the parameter `name`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub file: String,
+    pub class_name: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub py_files: Option<Vec<String>>,
+    pub jars: Option<Vec<String>>,
+    pub driver_memory: Option<String>,
+    pub driver_cores: Option<i64>,
+    pub executor_memory: Option<String>,
+    pub executor_cores: Option<i64>,
+    pub num_executors: Option<i64>,
+    pub queue: Option<String>,
+    pub conf: Option<HashMap<String, String>>,
+    pub parameters: Option<Vec<BatchTemplateParameter>>,
+}
+
+/// Parameter of a batch job template
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchTemplateParameter {
+    pub name: String,
+    pub description: Option<String>,
+    pub default: Option<String>,
+    pub choices: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LDAP_CONFIG: &'static str = r#"
+        [ldap]
+        url = "ldaps://ldap.example.com"
+        user_dn = "uid={},ou=people,dc=example,dc=com"
+
+        [livy_client]
+        url = "http://livy.example.com:8998"
+
+        [http]
+        addr = "127.0.0.1:8000"
+        num_threads = 4
+    "#;
+
+    #[test]
+    fn test_validate_without_audit() {
+        let conf: Config = toml::from_str(LDAP_CONFIG).unwrap();
+        assert!(conf.auth_required());
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_kill_notification_without_audit() {
+        let conf: Config = toml::from_str(&format!(r#"{}
+            [kill_notification.channel]
+            type = "webhook"
+            url = "https://chat.example.com/hooks/livy"
+        "#, LDAP_CONFIG)).unwrap();
+        assert!(conf.validate().is_err());
+
+        let conf: Config = toml::from_str(&format!(r#"{}
+            [audit]
+            path = "/var/log/livy-manager/audit.jsonl"
+
+            [kill_notification.channel]
+            type = "webhook"
+            url = "https://chat.example.com/hooks/livy"
+        "#, LDAP_CONFIG)).unwrap();
+        assert!(conf.validate().is_ok());
+    }
 }
@@ -23,6 +23,9 @@ pub const INDEX: &'static str = r##"
         table {
             margin-top: 0.5rem;
         }
+        h4 {
+            margin-top: 1rem;
+        }
     </style>
   </head>
   <body>
@@ -37,12 +40,21 @@ pub const INDEX: &'static str = r##"
     </nav>
 
     <div class="container">
+        <div id="cluster_errors"></div>
+        <div class="row">
+            <form class="form-inline">
+                <label for="cluster" class="mr-2">Cluster</label>
+                <select class="form-control form-control-sm" id="cluster"></select>
+            </form>
+        </div>
         <div class="row">
             <h4>Active Sessions</h4>
+            <a class="btn btn-outline-primary btn-sm ml-auto" id="new_session" href="/new_session">New Session</a>
             <table class="table table-hover table-sm">
                 <caption id="sessions_table_caption"></caption>
                 <thead class="thead-light">
                     <tr>
+                        <th scope="col">Cluster</th>
                         <th scope="col">ID</th>
                         <th scope="col">App ID</th>
                         <th scope="col">Proxy User</th>
@@ -55,6 +67,25 @@ pub const INDEX: &'static str = r##"
                 </tbody>
             </table>
         </div>
+        <div class="row">
+            <h4>Batches</h4>
+            <a class="btn btn-outline-primary btn-sm ml-auto" id="new_batch" href="/new_batch">New Batch</a>
+            <table class="table table-hover table-sm">
+                <caption id="batches_table_caption"></caption>
+                <thead class="thead-light">
+                    <tr>
+                        <th scope="col">Cluster</th>
+                        <th scope="col">ID</th>
+                        <th scope="col">App ID</th>
+                        <th scope="col">Proxy User</th>
+                        <th scope="col">State</th>
+                        <th scope="col">Operation</th>
+                    </tr>
+                </thead>
+                <tbody id="batches">
+                </tbody>
+            </table>
+        </div>
     </div>
 
     <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
@@ -62,6 +93,13 @@ pub const INDEX: &'static str = r##"
     <script src="https://cdnjs.cloudflare.com/ajax/libs/popper.js/1.12.3/umd/popper.min.js" integrity="sha384-vFJXuSJphROIrBnz7yo7oB41mKfc8JzQZiCq4NCceLEaO4IHwicKwpJf9c9IpFgh" crossorigin="anonymous"></script>
     <script src="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/js/bootstrap.min.js" integrity="sha384-alpBpkh1PFOepccYVYDB4do5UnbKysX5WZXm3XxPqe5iKTfUKjNkCk9SaVuEZflJ" crossorigin="anonymous"></script>
     <script type="text/javascript">
+        // Selected cluster. An empty string means all of the clusters.
+        var cluster = '';
+
+        function clusterUrl(c, path) {
+            return '/api/clusters/' + encodeURIComponent(c) + path;
+        }
+
         function isEmpty(o) {
             return o === null || o === undefined || o === '';
         }
@@ -94,46 +132,131 @@ pub const INDEX: &'static str = r##"
             return '<a href="' + sparkUiUrl + '" target="_blank"> ' + appId + '</a>';
         }
 
-        function killLink(id) {
+        function sessionLink(c, id) {
+            id = $.trim(id);
+
+            if (isEmpty(id)) {
+                return '-';
+            }
+
+            return '<a href="/clusters/' + encodeURIComponent(c) + '/sessions/' + id + '">' + id + '</a>';
+        }
+
+        function killLink(c, id) {
             id = $.trim(id);
 
             if (isEmpty(id)) {
                 return '';
             }
 
-            return '<a href="#" onclick="killSession(\'' + id + '\');">kill</a>';
+            return '<a href="#" onclick="killSession(\'' + c + '\', \'' + id + '\');">kill</a>';
         }
 
-        function killSession(id) {
-            if (!confirm('Are you sure to kill the session ' + id + '?')) {
+        function killBatchLink(c, id) {
+            id = $.trim(id);
+
+            if (isEmpty(id)) {
+                return '';
+            }
+
+            return '<a href="#" onclick="killBatch(\'' + c + '\', \'' + id + '\');">kill</a>';
+        }
+
+        function showClusterErrors(kind, clusters) {
+            $.each(clusters, function(_, c) {
+                if (isEmpty(c.error)) {
+                    return;
+                }
+
+                $('#cluster_errors').append(
+                    $('<div class="alert alert-warning" role="alert">').text(
+                        'Failed to fetch ' + kind + ' from the cluster ' + c.name + ': ' + c.error));
+            });
+        }
+
+        function aggregatedUrl(path) {
+            if (isEmpty(cluster)) {
+                return path;
+            }
+            return path + '?cluster=' + encodeURIComponent(cluster);
+        }
+
+        function killSession(c, id) {
+            var reason = prompt('Are you sure to kill the session ' + id + '? ' +
+                'Please enter the reason, which is required for the session of another user and is sent to its owner.');
+            if (reason === null) {
                 return;
             }
 
             $.ajax({
-                url: '/api/sessions/' + id,
+                url: clusterUrl(c, '/sessions/' + id),
                 method: 'DELETE',
                 contentType: 'application/json',
+                data: JSON.stringify({ reason: reason }),
             }).done(function() {
                 alert('Session ' + id + ' was killed successfully.');
-                location.href = '/';
+                location.reload();
             }).fail(function(d) {
-                alert('Failed to kill the session.');
+                var msg = 'Failed to kill the session.';
+                if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                    msg += ' ' + d.responseJSON.error;
+                }
+                alert(msg);
             });
         }
 
-        $(function() {
+        function killBatch(c, id) {
+            var reason = prompt('Are you sure to kill the batch ' + id + '? ' +
+                'Please enter the reason, which is required for the batch of another user and is sent to its owner.');
+            if (reason === null) {
+                return;
+            }
+
+            $.ajax({
+                url: clusterUrl(c, '/batches/' + id),
+                method: 'DELETE',
+                contentType: 'application/json',
+                data: JSON.stringify({ reason: reason }),
+            }).done(function() {
+                alert('Batch ' + id + ' was killed successfully.');
+                location.reload();
+            }).fail(function(d) {
+                var msg = 'Failed to kill the batch.';
+                if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                    msg += ' ' + d.responseJSON.error;
+                }
+                alert(msg);
+            });
+        }
+
+        function queryParam(key) {
+            var value = null;
+            $.each(document.location.search.substr(1).split('&'), function(_, q) {
+                var i = q.split('=');
+                if (i.length == 2 && i[0] === key) {
+                    value = decodeURIComponent(i[1]);
+                }
+            });
+            return value;
+        }
+
+        function loadSessions() {
             $.getJSON(
-                '/api/sessions'
-            ).done(function(sessions) {
+                aggregatedUrl('/api/sessions')
+            ).done(function(res) {
+                var sessions = res.items;
+                showClusterErrors('sessions', res.clusters);
+
                 $.each(sessions, function(_, session) {
                     $('#sessions').append(
                         '<tr>' +
-                            '<td>' + fmtStr(session.id)                           + '</td>' +
+                            '<td>' + fmtStr(session.cluster)                      + '</td>' +
+                            '<td>' + sessionLink(session.cluster, session.id)     + '</td>' +
                             '<td>' + appIdLink(session.appId, session.appInfo)    + '</td>' +
                             '<td>' + fmtStr(session.proxyUser)                    + '</td>' +
                             '<td>' + fmtStr(session.kind)                         + '</td>' +
                             '<td>' + fmtStr(session.state)                        + '</td>' +
-                            '<td>' + killLink(session.id)                         + '</td>' +
+                            '<td>' + killLink(session.cluster, session.id)        + '</td>' +
                         '</tr>');
                 });
 
@@ -141,6 +264,60 @@ pub const INDEX: &'static str = r##"
                     $('#sessions_table_caption').text('No active sessions to be shown.');
                 }
             });
+        }
+
+        function loadBatches() {
+            $.getJSON(
+                aggregatedUrl('/api/batches')
+            ).done(function(res) {
+                var batches = res.items;
+                showClusterErrors('batches', res.clusters);
+
+                $.each(batches, function(_, batch) {
+                    $('#batches').append(
+                        '<tr>' +
+                            '<td>' + fmtStr(batch.cluster)                        + '</td>' +
+                            '<td>' + fmtStr(batch.id)                             + '</td>' +
+                            '<td>' + appIdLink(batch.appId, batch.appInfo)        + '</td>' +
+                            '<td>' + fmtStr(batch.proxyUser)                      + '</td>' +
+                            '<td>' + fmtStr(batch.state)                          + '</td>' +
+                            '<td>' + killBatchLink(batch.cluster, batch.id)       + '</td>' +
+                        '</tr>');
+                });
+
+                if (batches.length == 0) {
+                    $('#batches_table_caption').text('No batches to be shown.');
+                }
+            });
+        }
+
+        $(function() {
+            $.getJSON(
+                '/api/clusters'
+            ).done(function(clusters) {
+                cluster = queryParam('cluster');
+                if (isEmpty(cluster) || $.inArray(cluster, clusters) < 0) {
+                    cluster = '';
+                }
+
+                $('#cluster').append($('<option>').val('').text('All'));
+                $.each(clusters, function(_, c) {
+                    $('#cluster').append($('<option>').val(c).text(c));
+                });
+                $('#cluster').val(cluster);
+
+                if (!isEmpty(cluster)) {
+                    $('#new_session').attr('href', '/new_session?cluster=' + encodeURIComponent(cluster));
+                    $('#new_batch').attr('href', '/new_batch?cluster=' + encodeURIComponent(cluster));
+                }
+
+                loadSessions();
+                loadBatches();
+            });
+
+            $('#cluster').change(function() {
+                location.href = '/?cluster=' + encodeURIComponent($(this).val());
+            });
 
             $.getJSON(
                 '/api/user_session'
@@ -149,12 +326,22 @@ pub const INDEX: &'static str = r##"
                     return;
                 }
 
+                $.ajaxSetup({
+                    headers: { 'X-CSRF-Token': user.csrf_token },
+                });
+
                 var userName = user.uid;
-                if (user.is_admin) {
-                    userName += ' (Admin)';
+                var roles = $.grep(user.roles, function(role) {
+                    return role !== 'user';
+                });
+                if (roles.length > 0) {
+                    userName += ' (' + roles.join(', ') + ')';
                 }
                 $('#user_name').text(userName);
 
+                if (user.api_tokens) {
+                    $('#navbar_right').append('<a class="nav-link" href="/tokens">API Tokens</a>');
+                }
                 $('#navbar_right').append('<a class="nav-link" href="/logout">Log Out</a>');
             });
         });
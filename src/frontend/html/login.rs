@@ -31,7 +31,8 @@ pub const LOGIN: &'static str = r##"
             <h4>Log In</h4>
         </div>
         <div class="row">
-            <form action="/login" method="post">
+            <form action="/login" method="post" {{password_login_hidden}}>
+              <input type="hidden" name="csrf_token" value="{{csrf_token}}">
               <div class="form-group">
                 <label for="uid">UID</label>
                 <input type="text" class="form-control" id="uid" name="uid" aria-describedby="emailHelp" placeholder="UID">
@@ -43,6 +44,9 @@ pub const LOGIN: &'static str = r##"
               <button type="submit" class="btn btn-primary">Log In</button>
             </form>
         </div>
+        <div class="row" {{oidc_login_hidden}}>
+            <a href="/login/oidc" class="btn btn-primary">Log In with Single Sign-On</a>
+        </div>
     </div>
 
     <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
@@ -60,7 +64,8 @@ pub const LOGIN: &'static str = r##"
             });
 
             if (queries['result'] === 'failed') {
-                alert('Authentication failed. Please make sure that the uid and password are correct.');
+                alert('Authentication failed. Please make sure that the uid and password are correct. ' +
+                      'After repeated failures, please wait a while before trying again.');
             }
         });
     </script>
@@ -2,3 +2,11 @@
 pub mod index;
 /// login
 pub mod login;
+/// new batch
+pub mod new_batch;
+/// new session
+pub mod new_session;
+/// session
+pub mod session;
+/// tokens
+pub mod tokens;
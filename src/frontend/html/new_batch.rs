@@ -0,0 +1,239 @@
+pub const NEW_BATCH: &'static str = r##"
+<!doctype html>
+<html lang="en">
+  <head>
+    <title>Livy Manager</title>
+
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1, shrink-to-fit=no">
+    <meta content="IE=edge" http-equiv="X-UA-Compatible">
+
+    <link rel="stylesheet" href="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/css/bootstrap.min.css" integrity="sha384-PsH8R72JQ3SOdhVi3uxftmaW6Vc51MKb0q5P2rRUpPvrszuE4W1povHYgTpBfshb" crossorigin="anonymous">
+    <style type="text/css">
+        body {
+            padding-top: 5rem;
+        }
+        .navbar-brand {
+            font-size: 1.5rem;
+        }
+        .navbar-text {
+            padding-left: 0.5rem;
+            padding-right: 0.5rem;
+        }
+        table {
+            margin-top: 0.5rem;
+        }
+    </style>
+  </head>
+  <body>
+    <nav class="navbar navbar-expand-md navbar-dark bg-dark fixed-top">
+        <a class="navbar-brand" href="/">Livy Manager</a>
+        <div class="collapse navbar-collapse" id="navbar">
+            <ul class="navbar-nav mr-auto"></ul>
+            <div id="navbar_right" class="navbar-nav navbar-right">
+                <div id="user_name" class="navbar-text"></div>
+            </div>
+        </div>
+    </nav>
+
+    <div class="container">
+        <div class="row">
+            <h4>New Batch</h4>
+        </div>
+        <div class="row">
+            <form id="new_batch_form" class="col-md-8">
+                <div class="form-group">
+                    <label for="cluster">Cluster</label>
+                    <select class="form-control" id="cluster"></select>
+                </div>
+                <div class="form-group">
+                    <label for="template">Template</label>
+                    <select class="form-control" id="template"></select>
+                    <small class="form-text text-muted" id="template_description"></small>
+                </div>
+                <dl class="row" id="template_detail"></dl>
+                <div id="parameters"></div>
+                <div class="form-group" id="proxy_user_group">
+                    <label for="proxy_user">Proxy User</label>
+                    <input type="text" class="form-control" id="proxy_user">
+                </div>
+                <button type="submit" class="btn btn-primary">Submit</button>
+            </form>
+        </div>
+    </div>
+
+    <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
+  crossorigin="anonymous"></script>
+    <script src="https://cdnjs.cloudflare.com/ajax/libs/popper.js/1.12.3/umd/popper.min.js" integrity="sha384-vFJXuSJphROIrBnz7yo7oB41mKfc8JzQZiCq4NCceLEaO4IHwicKwpJf9c9IpFgh" crossorigin="anonymous"></script>
+    <script src="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/js/bootstrap.min.js" integrity="sha384-alpBpkh1PFOepccYVYDB4do5UnbKysX5WZXm3XxPqe5iKTfUKjNkCk9SaVuEZflJ" crossorigin="anonymous"></script>
+    <script type="text/javascript">
+        var templates = [];
+
+        function isEmpty(o) {
+            return o === null || o === undefined || o === '';
+        }
+
+        function queryParam(key) {
+            var value = null;
+            $.each(document.location.search.substr(1).split('&'), function(_, q) {
+                var i = q.split('=');
+                if (i.length == 2 && i[0] === key) {
+                    value = decodeURIComponent(i[1]);
+                }
+            });
+            return value;
+        }
+
+        function loadClusters() {
+            $.getJSON(
+                '/api/clusters'
+            ).done(function(clusters) {
+                $.each(clusters, function(_, c) {
+                    $('#cluster').append($('<option>').val(c).text(c));
+                });
+
+                var cluster = queryParam('cluster');
+                if (!isEmpty(cluster) && $.inArray(cluster, clusters) >= 0) {
+                    $('#cluster').val(cluster);
+                }
+            });
+        }
+
+        function detail(name, value) {
+            if (isEmpty(value) || ($.isArray(value) && value.length == 0)) {
+                return;
+            }
+
+            if ($.isArray(value)) {
+                value = value.join(' ');
+            }
+
+            $('#template_detail').append($('<dt class="col-sm-3">').text(name));
+            $('#template_detail').append($('<dd class="col-sm-9">').text(value));
+        }
+
+        function showTemplate(template) {
+            $('#template_description').text('');
+            $('#template_detail').empty();
+            $('#parameters').empty();
+
+            if (isEmpty(template)) {
+                return;
+            }
+
+            $('#template_description').text(template.description || '');
+            detail('File', template.file);
+            detail('Class Name', template.class_name);
+            detail('Args', template.args);
+            detail('Python Files', template.py_files);
+
+            $.each(template.parameters || [], function(i, parameter) {
+                var id = 'parameter_' + i;
+                var group = $('<div class="form-group">');
+                group.append($('<label>').attr('for', id).text(parameter.name));
+
+                var input;
+                if (isEmpty(parameter.choices)) {
+                    input = $('<input type="text" class="form-control">');
+                } else {
+                    input = $('<select class="form-control">');
+                    $.each(parameter.choices, function(_, choice) {
+                        input.append($('<option>').val(choice).text(choice));
+                    });
+                }
+                input.attr('id', id).attr('data-name', parameter.name).addClass('parameter');
+                if (!isEmpty(parameter.default)) {
+                    input.val(parameter.default);
+                }
+                group.append(input);
+
+                if (!isEmpty(parameter.description)) {
+                    group.append($('<small class="form-text text-muted">').text(parameter.description));
+                }
+
+                $('#parameters').append(group);
+            });
+        }
+
+        function formData() {
+            var parameters = {};
+            $('.parameter').each(function() {
+                parameters[$(this).attr('data-name')] = $(this).val();
+            });
+
+            return {
+                template: $('#template').val(),
+                parameters: parameters,
+                proxy_user: $('#proxy_user').val(),
+            };
+        }
+
+        $(function() {
+            loadClusters();
+
+            $.getJSON(
+                '/api/batch_templates'
+            ).done(function(ts) {
+                templates = ts;
+                $.each(templates, function(_, template) {
+                    $('#template').append($('<option>').val(template.name).text(template.name));
+                });
+                showTemplate(templates[0]);
+            });
+
+            $('#template').change(function() {
+                var name = $(this).val();
+                showTemplate($.grep(templates, function(template) { return template.name === name; })[0]);
+            });
+
+            $('#new_batch_form').submit(function(e) {
+                e.preventDefault();
+
+                $.ajax({
+                    url: '/api/clusters/' + encodeURIComponent($('#cluster').val()) + '/batches',
+                    method: 'POST',
+                    contentType: 'application/json',
+                    data: JSON.stringify(formData()),
+                    dataType: 'json',
+                }).done(function(batch) {
+                    alert('Batch ' + batch.id + ' was submitted successfully.');
+                    location.href = '/?cluster=' + encodeURIComponent(batch.cluster);
+                }).fail(function(d) {
+                    var msg = 'Failed to submit the batch.';
+                    if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                        msg += ' ' + d.responseJSON.error;
+                    }
+                    alert(msg);
+                });
+            });
+
+            $.getJSON(
+                '/api/user_session'
+            ).done(function(user) {
+                if (user === null || user === undefined) {
+                    return;
+                }
+
+                $.ajaxSetup({
+                    headers: { 'X-CSRF-Token': user.csrf_token },
+                });
+
+                var userName = user.uid;
+                var roles = $.grep(user.roles, function(role) {
+                    return role !== 'user';
+                });
+                if (roles.length > 0) {
+                    userName += ' (' + roles.join(', ') + ')';
+                }
+                if (!user.is_admin) {
+                    $('#proxy_user').val(user.uid).prop('disabled', true);
+                }
+                $('#user_name').text(userName);
+
+                $('#navbar_right').append('<a class="nav-link" href="/logout">Log Out</a>');
+            });
+        });
+    </script>
+  </body>
+</html>
+"##;
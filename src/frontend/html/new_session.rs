@@ -0,0 +1,294 @@
+pub const NEW_SESSION: &'static str = r##"
+<!doctype html>
+<html lang="en">
+  <head>
+    <title>Livy Manager</title>
+
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1, shrink-to-fit=no">
+    <meta content="IE=edge" http-equiv="X-UA-Compatible">
+
+    <link rel="stylesheet" href="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/css/bootstrap.min.css" integrity="sha384-PsH8R72JQ3SOdhVi3uxftmaW6Vc51MKb0q5P2rRUpPvrszuE4W1povHYgTpBfshb" crossorigin="anonymous">
+    <style type="text/css">
+        body {
+            padding-top: 5rem;
+        }
+        .navbar-brand {
+            font-size: 1.5rem;
+        }
+        .navbar-text {
+            padding-left: 0.5rem;
+            padding-right: 0.5rem;
+        }
+        table {
+            margin-top: 0.5rem;
+        }
+    </style>
+  </head>
+  <body>
+    <nav class="navbar navbar-expand-md navbar-dark bg-dark fixed-top">
+        <a class="navbar-brand" href="/">Livy Manager</a>
+        <div class="collapse navbar-collapse" id="navbar">
+            <ul class="navbar-nav mr-auto"></ul>
+            <div id="navbar_right" class="navbar-nav navbar-right">
+                <div id="user_name" class="navbar-text"></div>
+            </div>
+        </div>
+    </nav>
+
+    <div class="container">
+        <div class="row">
+            <h4>New Session</h4>
+        </div>
+        <div class="row">
+            <form id="new_session_form" class="col-md-8">
+                <div class="form-group">
+                    <label for="cluster">Cluster</label>
+                    <select class="form-control" id="cluster"></select>
+                </div>
+                <div class="form-group">
+                    <label for="preset">Preset</label>
+                    <select class="form-control" id="preset">
+                        <option value="">-</option>
+                    </select>
+                </div>
+                <div class="form-group">
+                    <label for="kind">Kind</label>
+                    <select class="form-control" id="kind"></select>
+                </div>
+                <div class="form-group">
+                    <label for="name">Name</label>
+                    <input type="text" class="form-control" id="name">
+                </div>
+                <div class="form-group" id="proxy_user_group">
+                    <label for="proxy_user">Proxy User</label>
+                    <input type="text" class="form-control" id="proxy_user">
+                </div>
+                <div class="form-row">
+                    <div class="form-group col-md-6">
+                        <label for="driver_memory">Driver Memory</label>
+                        <input type="text" class="form-control" id="driver_memory" placeholder="e.g. 1g">
+                        <small class="form-text text-muted limit" id="driver_memory_limit"></small>
+                    </div>
+                    <div class="form-group col-md-6">
+                        <label for="driver_cores">Driver Cores</label>
+                        <input type="number" min="1" class="form-control" id="driver_cores">
+                        <small class="form-text text-muted limit" id="driver_cores_limit"></small>
+                    </div>
+                </div>
+                <div class="form-row">
+                    <div class="form-group col-md-6">
+                        <label for="executor_memory">Executor Memory</label>
+                        <input type="text" class="form-control" id="executor_memory" placeholder="e.g. 2g">
+                        <small class="form-text text-muted limit" id="executor_memory_limit"></small>
+                    </div>
+                    <div class="form-group col-md-6">
+                        <label for="executor_cores">Executor Cores</label>
+                        <input type="number" min="1" class="form-control" id="executor_cores">
+                        <small class="form-text text-muted limit" id="executor_cores_limit"></small>
+                    </div>
+                </div>
+                <div class="form-row">
+                    <div class="form-group col-md-6">
+                        <label for="num_executors">Number of Executors</label>
+                        <input type="number" min="1" class="form-control" id="num_executors">
+                        <small class="form-text text-muted limit" id="num_executors_limit"></small>
+                    </div>
+                    <div class="form-group col-md-6">
+                        <label for="queue">Queue</label>
+                        <input type="text" class="form-control" id="queue">
+                        <small class="form-text text-muted limit" id="queues_limit"></small>
+                    </div>
+                </div>
+                <div class="form-group">
+                    <label for="jars">Jars</label>
+                    <textarea class="form-control" id="jars" rows="3" placeholder="One path per line"></textarea>
+                </div>
+                <div class="form-group">
+                    <label for="conf">Spark Configuration</label>
+                    <textarea class="form-control" id="conf" rows="4" placeholder="key=value, one per line"></textarea>
+                </div>
+                <button type="submit" class="btn btn-primary">Create</button>
+            </form>
+        </div>
+    </div>
+
+    <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
+  crossorigin="anonymous"></script>
+    <script src="https://cdnjs.cloudflare.com/ajax/libs/popper.js/1.12.3/umd/popper.min.js" integrity="sha384-vFJXuSJphROIrBnz7yo7oB41mKfc8JzQZiCq4NCceLEaO4IHwicKwpJf9c9IpFgh" crossorigin="anonymous"></script>
+    <script src="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/js/bootstrap.min.js" integrity="sha384-alpBpkh1PFOepccYVYDB4do5UnbKysX5WZXm3XxPqe5iKTfUKjNkCk9SaVuEZflJ" crossorigin="anonymous"></script>
+    <script type="text/javascript">
+        var presets = [];
+        var resourceFields = ['driver_memory', 'driver_cores', 'executor_memory', 'executor_cores', 'num_executors', 'queue'];
+
+        function isEmpty(o) {
+            return o === null || o === undefined || o === '';
+        }
+
+        function lines(id) {
+            return $.map($('#' + id).val().split('\n'), function(line) {
+                line = $.trim(line);
+                return line === '' ? null : line;
+            });
+        }
+
+        function intVal(id) {
+            var v = $.trim($('#' + id).val());
+            return v === '' ? null : parseInt(v, 10);
+        }
+
+        function queryParam(key) {
+            var value = null;
+            $.each(document.location.search.substr(1).split('&'), function(_, q) {
+                var i = q.split('=');
+                if (i.length == 2 && i[0] === key) {
+                    value = decodeURIComponent(i[1]);
+                }
+            });
+            return value;
+        }
+
+        function loadClusters() {
+            $.getJSON(
+                '/api/clusters'
+            ).done(function(clusters) {
+                $.each(clusters, function(_, c) {
+                    $('#cluster').append($('<option>').val(c).text(c));
+                });
+
+                var cluster = queryParam('cluster');
+                if (!isEmpty(cluster) && $.inArray(cluster, clusters) >= 0) {
+                    $('#cluster').val(cluster);
+                }
+            });
+        }
+
+        function applyPreset(preset) {
+            if (isEmpty(preset)) {
+                return;
+            }
+
+            if (!isEmpty(preset.kind)) {
+                $('#kind').val(preset.kind);
+            }
+
+            $.each(resourceFields, function(_, field) {
+                $('#' + field).val(isEmpty(preset[field]) ? '' : preset[field]);
+            });
+
+            $('#jars').val((preset.jars || []).join('\n'));
+            $('#conf').val($.map(preset.conf || {}, function(v, k) { return k + '=' + v; }).join('\n'));
+        }
+
+        function showLimits(limits) {
+            if (isEmpty(limits)) {
+                return;
+            }
+
+            $.each(['driver_memory', 'driver_cores', 'executor_memory', 'executor_cores', 'num_executors'], function(_, field) {
+                if (!isEmpty(limits[field])) {
+                    $('#' + field + '_limit').text('Up to ' + limits[field]);
+                }
+            });
+
+            if (!isEmpty(limits.queues)) {
+                $('#queues_limit').text('One of ' + limits.queues.join(', '));
+            }
+        }
+
+        function formData() {
+            var conf = {};
+            $.each(lines('conf'), function(_, line) {
+                var i = line.indexOf('=');
+                if (i > 0) {
+                    conf[$.trim(line.substr(0, i))] = $.trim(line.substr(i + 1));
+                }
+            });
+
+            return {
+                kind: $('#kind').val(),
+                name: $('#name').val(),
+                proxy_user: $('#proxy_user').val(),
+                driver_memory: $('#driver_memory').val(),
+                driver_cores: intVal('driver_cores'),
+                executor_memory: $('#executor_memory').val(),
+                executor_cores: intVal('executor_cores'),
+                num_executors: intVal('num_executors'),
+                queue: $('#queue').val(),
+                jars: lines('jars'),
+                conf: conf,
+            };
+        }
+
+        $(function() {
+            loadClusters();
+
+            $.getJSON(
+                '/api/new_session'
+            ).done(function(conf) {
+                $.each(conf.kinds, function(_, kind) {
+                    $('#kind').append($('<option>').val(kind).text(kind));
+                });
+
+                presets = conf.presets;
+                $.each(presets, function(i, preset) {
+                    $('#preset').append($('<option>').val(i).text(preset.name));
+                });
+
+                showLimits(conf.limits);
+            });
+
+            $('#preset').change(function() {
+                applyPreset(presets[$(this).val()]);
+            });
+
+            $('#new_session_form').submit(function(e) {
+                e.preventDefault();
+
+                $.ajax({
+                    url: '/api/clusters/' + encodeURIComponent($('#cluster').val()) + '/sessions',
+                    method: 'POST',
+                    contentType: 'application/json',
+                    data: JSON.stringify(formData()),
+                    dataType: 'json',
+                }).done(function(session) {
+                    location.href = '/clusters/' + encodeURIComponent(session.cluster) + '/sessions/' + session.id;
+                }).fail(function(d) {
+                    var msg = 'Failed to create a session.';
+                    if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                        msg += ' ' + d.responseJSON.error;
+                    }
+                    alert(msg);
+                });
+            });
+
+            $.getJSON(
+                '/api/user_session'
+            ).done(function(user) {
+                if (user === null || user === undefined) {
+                    return;
+                }
+
+                $.ajaxSetup({
+                    headers: { 'X-CSRF-Token': user.csrf_token },
+                });
+
+                var userName = user.uid;
+                var roles = $.grep(user.roles, function(role) {
+                    return role !== 'user';
+                });
+                if (roles.length > 0) {
+                    userName += ' (' + roles.join(', ') + ')';
+                }
+                if (!user.is_admin) {
+                    $('#proxy_user').val(user.uid).prop('disabled', true);
+                }
+                $('#user_name').text(userName);
+
+                $('#navbar_right').append('<a class="nav-link" href="/logout">Log Out</a>');
+            });
+        });
+    </script>
+  </body>
+</html>
+"##;
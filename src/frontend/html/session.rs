@@ -0,0 +1,340 @@
+pub const SESSION: &'static str = r##"
+<!doctype html>
+<html lang="en">
+  <head>
+    <title>Livy Manager</title>
+
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1, shrink-to-fit=no">
+    <meta content="IE=edge" http-equiv="X-UA-Compatible">
+
+    <link rel="stylesheet" href="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/css/bootstrap.min.css" integrity="sha384-PsH8R72JQ3SOdhVi3uxftmaW6Vc51MKb0q5P2rRUpPvrszuE4W1povHYgTpBfshb" crossorigin="anonymous">
+    <style type="text/css">
+        body {
+            padding-top: 5rem;
+        }
+        .navbar-brand {
+            font-size: 1.5rem;
+        }
+        .navbar-text {
+            padding-left: 0.5rem;
+            padding-right: 0.5rem;
+        }
+        table {
+            margin-top: 0.5rem;
+        }
+        .statement-code {
+            max-width: 30rem;
+            white-space: pre-wrap;
+            font-size: 0.8rem;
+        }
+        #log {
+            height: 30rem;
+            overflow: auto;
+            padding: 0.5rem;
+            width: 100%;
+            background-color: #f8f9fa;
+            font-size: 0.8rem;
+        }
+    </style>
+  </head>
+  <body>
+    <nav class="navbar navbar-expand-md navbar-dark bg-dark fixed-top">
+        <a class="navbar-brand" href="/">Livy Manager</a>
+        <div class="collapse navbar-collapse" id="navbar">
+            <ul class="navbar-nav mr-auto"></ul>
+            <div id="navbar_right" class="navbar-nav navbar-right">
+                <div id="user_name" class="navbar-text"></div>
+            </div>
+        </div>
+    </nav>
+
+    <div class="container">
+        <div class="row">
+            <h4 id="session_title">Session</h4>
+            <table class="table table-sm">
+                <thead class="thead-light">
+                    <tr>
+                        <th scope="col">ID</th>
+                        <th scope="col">App ID</th>
+                        <th scope="col">Proxy User</th>
+                        <th scope="col">Kind</th>
+                        <th scope="col">State</th>
+                    </tr>
+                </thead>
+                <tbody id="session">
+                </tbody>
+            </table>
+        </div>
+        <div class="row">
+            <h4>Statements</h4>
+            <table class="table table-hover table-sm">
+                <caption id="statements_table_caption"></caption>
+                <thead class="thead-light">
+                    <tr>
+                        <th scope="col">ID</th>
+                        <th scope="col">Code</th>
+                        <th scope="col">State</th>
+                        <th scope="col">Progress</th>
+                        <th scope="col">Output</th>
+                        <th scope="col">Operation</th>
+                    </tr>
+                </thead>
+                <tbody id="statements">
+                </tbody>
+            </table>
+        </div>
+        <div class="row">
+            <h4>Log</h4>
+        </div>
+        <div class="row">
+            <div class="btn-group btn-group-sm" role="group">
+                <button id="load_older" type="button" class="btn btn-outline-secondary" onclick="loadOlder();">Load Older</button>
+                <button id="follow_tail" type="button" class="btn btn-outline-secondary" onclick="toggleFollowTail();">Follow Tail</button>
+            </div>
+        </div>
+        <div class="row">
+            <pre id="log"></pre>
+        </div>
+    </div>
+
+    <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
+  crossorigin="anonymous"></script>
+    <script src="https://cdnjs.cloudflare.com/ajax/libs/popper.js/1.12.3/umd/popper.min.js" integrity="sha384-vFJXuSJphROIrBnz7yo7oB41mKfc8JzQZiCq4NCceLEaO4IHwicKwpJf9c9IpFgh" crossorigin="anonymous"></script>
+    <script src="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/js/bootstrap.min.js" integrity="sha384-alpBpkh1PFOepccYVYDB4do5UnbKysX5WZXm3XxPqe5iKTfUKjNkCk9SaVuEZflJ" crossorigin="anonymous"></script>
+    <script type="text/javascript">
+        function isEmpty(o) {
+            return o === null || o === undefined || o === '';
+        }
+
+        function fmtStr(o) {
+            var s = $.trim(o);
+            if (s === '') {
+                return '-';
+            }
+            return s;
+        }
+
+        function appIdLink(appId, appInfo) {
+            appId = $.trim(appId);
+
+            if (isEmpty(appId)) {
+                return '-';
+            }
+
+            if (isEmpty(appInfo)) {
+                return appId;
+            }
+
+            var sparkUiUrl = $.trim(appInfo.sparkUiUrl);
+
+            if (isEmpty(sparkUiUrl)) {
+                return appId;
+            }
+
+            return '<a href="' + sparkUiUrl + '" target="_blank"> ' + appId + '</a>';
+        }
+
+        function escapeHtml(o) {
+            return $('<div>').text(fmtStr(o)).html();
+        }
+
+        function fmtProgress(progress) {
+            if (isEmpty(progress)) {
+                return '-';
+            }
+            return Math.round(progress * 100) + '%';
+        }
+
+        function cancelLink(statement) {
+            if (isEmpty(statement.id) || (statement.state !== 'waiting' && statement.state !== 'running')) {
+                return '';
+            }
+
+            return '<a href="#" onclick="cancelStatement(\'' + statement.id + '\');">cancel</a>';
+        }
+
+        function cancelStatement(id) {
+            if (!confirm('Are you sure to cancel the statement ' + id + '?')) {
+                return;
+            }
+
+            $.ajax({
+                url: sessionUrl + '/statements/' + id + '/cancel',
+                method: 'POST',
+                contentType: 'application/json',
+            }).done(function() {
+                alert('Statement ' + id + ' was cancelled successfully.');
+                loadStatements();
+            }).fail(function(d) {
+                alert('Failed to cancel the statement.');
+            });
+        }
+
+        function loadStatements() {
+            $.getJSON(
+                sessionUrl + '/statements'
+            ).done(function(statements) {
+                $('#statements').empty();
+                $('#statements_table_caption').text('');
+
+                $.each(statements, function(_, statement) {
+                    $('#statements').append(
+                        '<tr>' +
+                            '<td>' + fmtStr(statement.id)                                             + '</td>' +
+                            '<td><pre class="statement-code">' + escapeHtml(statement.code) + '</pre></td>' +
+                            '<td>' + fmtStr(statement.state)                                          + '</td>' +
+                            '<td>' + fmtProgress(statement.progress)                                  + '</td>' +
+                            '<td>' + escapeHtml(statement.output)                                     + '</td>' +
+                            '<td>' + cancelLink(statement)                                            + '</td>' +
+                        '</tr>');
+                });
+
+                if (statements.length == 0) {
+                    $('#statements_table_caption').text('No statements to be shown.');
+                }
+            }).fail(function() {
+                alert('Failed to load the statements.');
+            });
+        }
+
+        // The path of this page is /clusters/:cluster/sessions/:id.
+        var path = location.pathname.split('/');
+        var cluster = decodeURIComponent(path[2]);
+        var sessionId = path[4];
+        var sessionUrl = '/api/clusters/' + encodeURIComponent(cluster) + '/sessions/' + sessionId;
+        var logUrl = sessionUrl + '/log';
+        var pageSize = 100;
+        var followInterval = 3000;
+
+        // Offsets of the first line and the line next to the last one shown in the log view
+        var logFrom = 0;
+        var logTo = 0;
+        var followTimer = null;
+
+        function logLines(log) {
+            if (isEmpty(log) || isEmpty(log.log)) {
+                return [];
+            }
+            return log.log;
+        }
+
+        function updateLoadOlder() {
+            $('#load_older').prop('disabled', logFrom <= 0);
+        }
+
+        function scrollToBottom() {
+            var view = $('#log');
+            view.scrollTop(view.prop('scrollHeight'));
+        }
+
+        function loadTail() {
+            $.getJSON(logUrl, { size: pageSize }).done(function(log) {
+                var lines = logLines(log);
+                logFrom = log.from || 0;
+                logTo = logFrom + lines.length;
+                $('#log').text(lines.join('\n'));
+                updateLoadOlder();
+                scrollToBottom();
+            }).fail(function() {
+                alert('Failed to load the session log.');
+            });
+        }
+
+        function loadOlder() {
+            if (logFrom <= 0) {
+                return;
+            }
+
+            var from = Math.max(0, logFrom - pageSize);
+
+            $.getJSON(logUrl, { from: from, size: logFrom - from }).done(function(log) {
+                var lines = logLines(log);
+                if (lines.length == 0) {
+                    return;
+                }
+                var view = $('#log');
+                var current = view.text();
+                view.text(lines.join('\n') + (current === '' ? '' : '\n' + current));
+                logFrom = from;
+                updateLoadOlder();
+                view.scrollTop(0);
+            }).fail(function() {
+                alert('Failed to load the session log.');
+            });
+        }
+
+        function loadNewer() {
+            $.getJSON(logUrl, { from: logTo, size: pageSize }).done(function(log) {
+                var lines = logLines(log);
+                if (lines.length == 0) {
+                    return;
+                }
+                var view = $('#log');
+                var current = view.text();
+                view.text((current === '' ? '' : current + '\n') + lines.join('\n'));
+                logTo += lines.length;
+                scrollToBottom();
+            });
+        }
+
+        function toggleFollowTail() {
+            if (followTimer === null) {
+                loadNewer();
+                followTimer = setInterval(loadNewer, followInterval);
+                $('#follow_tail').addClass('active');
+            } else {
+                clearInterval(followTimer);
+                followTimer = null;
+                $('#follow_tail').removeClass('active');
+            }
+        }
+
+        $(function() {
+            $('#session_title').text('Session ' + sessionId + ' (' + cluster + ')');
+
+            $.getJSON(
+                sessionUrl
+            ).done(function(session) {
+                $('#session').append(
+                    '<tr>' +
+                        '<td>' + fmtStr(session.id)                           + '</td>' +
+                        '<td>' + appIdLink(session.appId, session.appInfo)    + '</td>' +
+                        '<td>' + fmtStr(session.proxyUser)                    + '</td>' +
+                        '<td>' + fmtStr(session.kind)                         + '</td>' +
+                        '<td>' + fmtStr(session.state)                        + '</td>' +
+                    '</tr>');
+            }).fail(function() {
+                alert('Failed to load the session.');
+            });
+
+            loadStatements();
+            loadTail();
+
+            $.getJSON(
+                '/api/user_session'
+            ).done(function(user) {
+                if (user === null || user === undefined) {
+                    return;
+                }
+
+                $.ajaxSetup({
+                    headers: { 'X-CSRF-Token': user.csrf_token },
+                });
+
+                var userName = user.uid;
+                var roles = $.grep(user.roles, function(role) {
+                    return role !== 'user';
+                });
+                if (roles.length > 0) {
+                    userName += ' (' + roles.join(', ') + ')';
+                }
+                $('#user_name').text(userName);
+
+                $('#navbar_right').append('<a class="nav-link" href="/logout">Log Out</a>');
+            });
+        });
+    </script>
+  </body>
+</html>
+"##;
@@ -0,0 +1,214 @@
+pub const TOKENS: &'static str = r##"
+<!doctype html>
+<html lang="en">
+  <head>
+    <title>Livy Manager</title>
+
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1, shrink-to-fit=no">
+    <meta content="IE=edge" http-equiv="X-UA-Compatible">
+
+    <link rel="stylesheet" href="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/css/bootstrap.min.css" integrity="sha384-PsH8R72JQ3SOdhVi3uxftmaW6Vc51MKb0q5P2rRUpPvrszuE4W1povHYgTpBfshb" crossorigin="anonymous">
+    <style type="text/css">
+        body {
+            padding-top: 5rem;
+        }
+        .navbar-brand {
+            font-size: 1.5rem;
+        }
+        .navbar-text {
+            padding-left: 0.5rem;
+            padding-right: 0.5rem;
+        }
+        table {
+            margin-top: 0.5rem;
+        }
+        h4 {
+            margin-top: 1rem;
+        }
+    </style>
+  </head>
+  <body>
+    <nav class="navbar navbar-expand-md navbar-dark bg-dark fixed-top">
+        <a class="navbar-brand" href="/">Livy Manager</a>
+        <div class="collapse navbar-collapse" id="navbar">
+            <ul class="navbar-nav mr-auto"></ul>
+            <div id="navbar_right" class="navbar-nav navbar-right">
+                <div id="user_name" class="navbar-text"></div>
+            </div>
+        </div>
+    </nav>
+
+    <div class="container">
+        <div class="row">
+            <h4>New API Token</h4>
+        </div>
+        <div class="row">
+            <form id="new_token_form" class="col-md-8">
+                <div class="form-group">
+                    <label for="name">Name</label>
+                    <input type="text" class="form-control" id="name" placeholder="on-call script" required>
+                </div>
+                <div class="form-group">
+                    <label for="scope">Scope</label>
+                    <select class="form-control" id="scope">
+                        <option value="read_only">Read-only</option>
+                        <option value="read_write">Read-write</option>
+                    </select>
+                </div>
+                <div class="form-group">
+                    <label for="expires_in_days">Expires in (days)</label>
+                    <input type="number" class="form-control" id="expires_in_days" min="1" placeholder="Maximum">
+                </div>
+                <button type="submit" class="btn btn-primary">Create</button>
+            </form>
+        </div>
+        <div class="row" id="new_token" hidden>
+            <div class="alert alert-success col-md-8" role="alert">
+                Please copy the token now. It will not be shown again.
+                <pre><code id="new_token_value"></code></pre>
+                <small>Send it as <code>Authorization: Bearer &lt;token&gt;</code> to the JSON API.</small>
+            </div>
+        </div>
+        <div class="row">
+            <h4>API Tokens</h4>
+            <table class="table table-hover table-sm">
+                <caption id="tokens_table_caption"></caption>
+                <thead class="thead-light">
+                    <tr>
+                        <th scope="col">Name</th>
+                        <th scope="col">Scope</th>
+                        <th scope="col">Created</th>
+                        <th scope="col">Expires</th>
+                        <th scope="col"></th>
+                    </tr>
+                </thead>
+                <tbody id="tokens"></tbody>
+            </table>
+        </div>
+    </div>
+
+    <script src="https://code.jquery.com/jquery-3.2.1.min.js" integrity="sha256-hwg4gsxgFZhOsEEamdOYGBf13FyQuiTwlAQgxVSNgt4="
+  crossorigin="anonymous"></script>
+    <script src="https://cdnjs.cloudflare.com/ajax/libs/popper.js/1.12.3/umd/popper.min.js" integrity="sha384-vFJXuSJphROIrBnz7yo7oB41mKfc8JzQZiCq4NCceLEaO4IHwicKwpJf9c9IpFgh" crossorigin="anonymous"></script>
+    <script src="https://maxcdn.bootstrapcdn.com/bootstrap/4.0.0-beta.2/js/bootstrap.min.js" integrity="sha384-alpBpkh1PFOepccYVYDB4do5UnbKysX5WZXm3XxPqe5iKTfUKjNkCk9SaVuEZflJ" crossorigin="anonymous"></script>
+    <script type="text/javascript">
+        function isEmpty(o) {
+            return o === null || o === undefined || o === '';
+        }
+
+        function fmtTime(secs) {
+            if (isEmpty(secs)) {
+                return 'Never';
+            }
+            return new Date(secs * 1000).toLocaleString();
+        }
+
+        function revokeToken(id, name) {
+            if (!confirm('Are you sure to revoke the API token ' + name + '?')) {
+                return;
+            }
+
+            $.ajax({
+                url: '/api/tokens/' + encodeURIComponent(id),
+                method: 'DELETE',
+            }).done(function() {
+                loadTokens();
+            }).fail(function(d) {
+                alert('Failed to revoke the API token.');
+            });
+        }
+
+        function loadTokens() {
+            $.getJSON(
+                '/api/tokens'
+            ).done(function(tokens) {
+                $('#tokens').empty();
+                $('#tokens_table_caption').text('');
+
+                $.each(tokens, function(_, token) {
+                    var revoke = $('<a href="#">').text('revoke').click(function(e) {
+                        e.preventDefault();
+                        revokeToken(token.id, token.name);
+                    });
+
+                    $('#tokens').append($('<tr>').append(
+                        $('<td>').text(token.name),
+                        $('<td>').text(token.scope),
+                        $('<td>').text(fmtTime(token.created_at)),
+                        $('<td>').text(fmtTime(token.expires_at)),
+                        $('<td>').append(revoke)
+                    ));
+                });
+
+                if (tokens.length == 0) {
+                    $('#tokens_table_caption').text('No API tokens to be shown.');
+                }
+            }).fail(function(d) {
+                var msg = 'Failed to fetch the API tokens.';
+                if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                    msg += ' ' + d.responseJSON.error;
+                }
+                $('#tokens_table_caption').text(msg);
+            });
+        }
+
+        $(function() {
+            $('#new_token_form').submit(function(e) {
+                e.preventDefault();
+
+                var expiresInDays = $('#expires_in_days').val();
+
+                $.ajax({
+                    url: '/api/tokens',
+                    method: 'POST',
+                    contentType: 'application/json',
+                    data: JSON.stringify({
+                        name: $('#name').val(),
+                        scope: $('#scope').val(),
+                        expires_in_days: isEmpty(expiresInDays) ? null : parseInt(expiresInDays, 10),
+                    }),
+                    dataType: 'json',
+                }).done(function(token) {
+                    $('#new_token_value').text(token.token);
+                    $('#new_token').prop('hidden', false);
+                    $('#new_token_form')[0].reset();
+                    loadTokens();
+                }).fail(function(d) {
+                    var msg = 'Failed to create the API token.';
+                    if (!isEmpty(d.responseJSON) && !isEmpty(d.responseJSON.error)) {
+                        msg += ' ' + d.responseJSON.error;
+                    }
+                    alert(msg);
+                });
+            });
+
+            $.getJSON(
+                '/api/user_session'
+            ).done(function(user) {
+                if (user === null || user === undefined) {
+                    return;
+                }
+
+                $.ajaxSetup({
+                    headers: { 'X-CSRF-Token': user.csrf_token },
+                });
+
+                var userName = user.uid;
+                var roles = $.grep(user.roles, function(role) {
+                    return role !== 'user';
+                });
+                if (roles.length > 0) {
+                    userName += ' (' + roles.join(', ') + ')';
+                }
+                $('#user_name').text(userName);
+
+                $('#navbar_right').append('<a class="nav-link" href="/logout">Log Out</a>');
+
+                loadTokens();
+            });
+        });
+    </script>
+  </body>
+</html>
+"##;
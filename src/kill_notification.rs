@@ -0,0 +1,221 @@
+use base64;
+use config::{self, NotificationChannel};
+use curl::easy::{Easy2, Handler, List, WriteError};
+use ldap;
+use lettre::smtp::authentication::Credentials;
+use lettre::smtp::{SMTP_PORT, SUBMISSIONS_PORT};
+use lettre::{ClientSecurity, ClientTlsParameters, EmailAddress, Envelope, SendableEmail, SmtpClient, Transport};
+use native_tls::TlsConnector;
+use new_batch;
+use serde_json;
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+use time;
+use url::Url;
+
+/// Timeout of the delivery of a notification in seconds
+const TIMEOUT_SECS: u64 = 30;
+const DEFAULT_SUBJECT: &'static str = "Your Livy {kind} {id} on {cluster} was killed";
+const DEFAULT_MESSAGE: &'static str = "Your Livy {kind} {id} (application {app_id}) on {cluster} \
+                                       was killed by {killed_by}.\n\nReason: {reason}\n";
+
+/// Kill of a session or batch by a user other than its proxy user
+#[derive(Clone, Debug, Serialize)]
+pub struct Kill {
+    /// "session" or "batch"
+    pub kind: &'static str,
+    pub cluster: String,
+    pub id: i64,
+    pub proxy_user: String,
+    pub app_id: Option<String>,
+    /// Uid of the user who killed it
+    pub killed_by: String,
+    pub reason: String,
+}
+
+impl Kill {
+    /// Returns the values of the placeholders of the templates.
+    fn values(&self) -> HashMap<String, String> {
+        let mut values = HashMap::new();
+        values.insert("kind".to_string(), self.kind.to_string());
+        values.insert("cluster".to_string(), self.cluster.clone());
+        values.insert("id".to_string(), self.id.to_string());
+        values.insert("proxy_user".to_string(), self.proxy_user.clone());
+        values.insert("app_id".to_string(), self.app_id.clone().unwrap_or_else(|| "unknown".to_string()));
+        values.insert("killed_by".to_string(), self.killed_by.clone());
+        values.insert("reason".to_string(), self.reason.clone());
+        values
+    }
+}
+
+/// Body of a webhook request
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    /// Message, which chat services such as Slack post
+    text: &'a str,
+    subject: &'a str,
+    kill: &'a Kill,
+}
+
+/// Notifies the proxy user of `kill` through the channel of `conf`.
+pub fn send(conf: &config::KillNotification, kill: &Kill) -> Result<(), String> {
+    let values = kill.values();
+    let subject = new_batch::substitute(conf.subject.as_ref().map_or(DEFAULT_SUBJECT, String::as_str), &values);
+    let message = new_batch::substitute(conf.message.as_ref().map_or(DEFAULT_MESSAGE, String::as_str), &values);
+
+    match conf.channel {
+        NotificationChannel::Webhook { ref url, ref ca_file } => {
+            let payload = WebhookPayload { text: &message, subject: &subject, kill };
+            let body = serde_json::to_vec(&payload).map_err(|err| format!("{}", err))?;
+            post(url, ca_file.as_ref(), body)
+        },
+        NotificationChannel::Smtp { ref url, ref username, ref password, ref from, ref to, ref ca_file, require_tls } => {
+            let to = single_line(&new_batch::substitute(to, &values));
+            let from = single_line(from);
+            let mail = format!(
+                "Date: {}\r\nFrom: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+                 Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}\r\n",
+                time::now().rfc822z(),
+                from,
+                to,
+                encode_header(&single_line(&subject)),
+                message.replace("\r\n", "\n").replace('\n', "\r\n"),
+            );
+
+            let credentials = username.as_ref()
+                .map(|username| Credentials::new(username.clone(), password.clone().unwrap_or_default()));
+            let res = send_mail(url, credentials, ca_file.as_ref(), require_tls.unwrap_or(true),
+                                address(&from), address(&to), mail.into_bytes());
+
+            res.map_err(|err| format!("failed to send a mail to {} through {}: {}", to, url, err))
+        },
+    }
+}
+
+/// POSTs the JSON `body` to `url`.
+fn post(url: &str, ca_file: Option<&String>, body: Vec<u8>) -> Result<(), String> {
+    let mut easy = Easy2::new(Transfer { response: Vec::new() });
+
+    let res = (|| {
+        easy.url(url)?;
+        easy.timeout(Duration::from_secs(TIMEOUT_SECS))?;
+
+        if let Some(ca_file) = ca_file {
+            easy.cainfo(ca_file)?;
+        }
+
+        let mut headers = List::new();
+        headers.append("Content-Type: application/json")?;
+        easy.http_headers(headers)?;
+        easy.post(true)?;
+        easy.post_fields_copy(&body)?;
+        easy.perform()?;
+        easy.response_code()
+    })();
+
+    match res {
+        Ok(code) if code >= 200 && code < 300 => Ok(()),
+        Ok(code) => Err(format!("{} returned {}: {}", url, code, String::from_utf8_lossy(&easy.get_ref().response))),
+        Err(err) => Err(format!("failed to request {}: {}", url, err)),
+    }
+}
+
+/// Sends `mail` from `from` to `to` through the SMTP server on `url`.
+///
+/// STARTTLS is required on smtp:// if `require_tls` is `true`, and smtps://
+/// is always encrypted.
+fn send_mail(url: &str, credentials: Option<Credentials>, ca_file: Option<&String>, require_tls: bool,
+             from: &str, to: &str, mail: Vec<u8>) -> Result<(), Box<Error>> {
+    let url = Url::parse(url)?;
+    let host = url.host_str().ok_or("no host in the URL")?.to_string();
+    let connector = match ca_file {
+        Some(ca_file) => ldap::tls_connector(ca_file)?,
+        None => TlsConnector::new()?,
+    };
+    let tls = ClientTlsParameters::new(host.clone(), connector);
+
+    let (security, default_port) = match url.scheme() {
+        "smtps" => (ClientSecurity::Wrapper(tls), SUBMISSIONS_PORT),
+        "smtp" if require_tls => (ClientSecurity::Required(tls), SMTP_PORT),
+        "smtp" => (ClientSecurity::Opportunistic(tls), SMTP_PORT),
+        scheme => return Err(From::from(format!("unsupported scheme: {}", scheme))),
+    };
+
+    let mut client = SmtpClient::new((host.as_str(), url.port().unwrap_or(default_port)), security)?
+        .timeout(Some(Duration::from_secs(TIMEOUT_SECS)));
+    if let Some(credentials) = credentials {
+        client = client.credentials(credentials);
+    }
+
+    let envelope = Envelope::new(Some(EmailAddress::new(from.to_string())?), vec![EmailAddress::new(to.to_string())?])?;
+    client.transport().send(SendableEmail::new(envelope, String::new(), mail))?;
+    Ok(())
+}
+
+/// Returns `s` with its line breaks replaced with spaces, so that it
+/// cannot inject header fields.
+fn single_line(s: &str) -> String {
+    s.chars().map(|c| if c == '\r' || c == '\n' { ' ' } else { c }).collect()
+}
+
+/// Returns the address in `mailbox`, such as `livy@example.com` in
+/// `Livy Manager <livy@example.com>`.
+fn address(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Encodes a header value which is not ASCII by RFC 2047.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Response of the server to which curl posts
+struct Transfer {
+    response: Vec<u8>,
+}
+
+impl Handler for Transfer {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.response.extend_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values() {
+        let kill = Kill {
+            kind: "session",
+            cluster: "prod".to_string(),
+            id: 3,
+            proxy_user: "alice".to_string(),
+            app_id: None,
+            killed_by: "bob".to_string(),
+            reason: "{proxy_user} used the whole queue".to_string(),
+        };
+
+        assert_eq!(
+            "Your Livy session 3 (application unknown) on prod was killed by bob.\n\nReason: {proxy_user} used the whole queue\n",
+            new_batch::substitute(DEFAULT_MESSAGE, &kill.values())
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        assert_eq!("a  b", single_line("a\r\nb"));
+        assert_eq!("livy@example.com", address("Livy Manager <livy@example.com>"));
+        assert_eq!("livy@example.com", address(" livy@example.com "));
+        assert_eq!("=?UTF-8?B?44K744OD44K344On44Oz?=", encode_header("セッション"));
+    }
+}
@@ -1,23 +1,404 @@
-use config::LDAP;
-use ldap3::{LdapConn, Scope};
+use authn::AuthError;
+use authz::{self, Role, Team};
+use config::{self, GroupMembership, ProxyUserAlias, LDAP};
+use ldap3::{dn_escape, ldap_escape, LdapConn, LdapConnSettings, Scope, SearchEntry};
+use native_tls::{Certificate, TlsConnector};
 use server::UserSession;
+use std::collections::HashSet;
 use std::error::Error;
+use std::fs::File;
+use std::io::prelude::*;
 
-pub fn auth(conf: &LDAP, uid: &str, password: &str) -> Result<UserSession, Box<Error>> {
-    let ldap = LdapConn::new(conf.url.as_str())?;
+const PEM_CERTIFICATE_BEGIN: &'static str = "-----BEGIN CERTIFICATE-----";
 
-    let user_dn = conf.user_dn.replace("{}", uid);
+/// Result code of the LDAP operations whose base object does not exist
+const NO_SUCH_OBJECT: u32 = 32;
+/// Result code of the binds with a wrong DN or password
+const INVALID_CREDENTIALS: u32 = 49;
 
-    ldap.simple_bind(user_dn.as_str(), password)?.success()?;
+/// Maximum depth of nested groups which are followed
+const MAX_NESTING_DEPTH: usize = 10;
 
-    let (res, _) = ldap.search(conf.admin_group_dn.as_str(), Scope::Subtree, format!("member={}", user_dn).as_str(), Vec::<&'static str>::new())?.success()?;
+pub fn auth(conf: &LDAP, teams: &[config::Team], proxy_user_aliases: &[ProxyUserAlias], uid: &str, password: &str) -> Result<UserSession, AuthError> {
+    // A simple bind with an empty password is an unauthenticated bind,
+    // which succeeds on many LDAP servers.
+    if password.is_empty() {
+        return Err(AuthError::InvalidCredentials("empty password".to_string()));
+    }
 
-    let is_admin = res.len() == 1;
+    let mut ldap = connect(conf).map_err(AuthError::unavailable)?;
+
+    let user_dn = find_user_dn(&mut ldap, conf, uid)?;
+
+    let res = ldap.simple_bind(user_dn.as_str(), password).map_err(AuthError::unavailable)?;
+    if res.rc == INVALID_CREDENTIALS {
+        return Err(AuthError::InvalidCredentials(format!("{}", res)));
+    }
+    res.success().map_err(AuthError::unavailable)?;
+
+    user_session(&mut ldap, conf, teams, proxy_user_aliases, &user_dn, uid).map_err(AuthError::unavailable)
+}
+
+/// Returns the login session of the user who has bound as `user_dn`.
+fn user_session(ldap: &mut LdapConn, conf: &LDAP, teams: &[config::Team], proxy_user_aliases: &[ProxyUserAlias], user_dn: &str, uid: &str) -> Result<UserSession, Box<Error>> {
+    let mut roles = conf.default_roles();
+
+    let admin_group_dns = conf.admin_group_dns();
+    let mapped_roles = conf.roles.iter().flat_map(|roles| roles.iter())
+        .map(|role| (role.role, &role.group_dns))
+        .chain(Some((Role::Admin, &admin_group_dns)));
+
+    for (role, group_dns) in mapped_roles {
+        if !roles.contains(&role) && is_member_of_any(ldap, conf, user_dn, uid, group_dns)? {
+            roles.push(role);
+        }
+    }
+
+    let proxy_users = find_proxy_users(ldap, proxy_user_aliases, user_dn, uid)?;
+    let teams = find_teams(ldap, conf, teams, proxy_user_aliases, user_dn, uid)?;
 
     let uid = String::from(uid);
 
     Ok(UserSession {
         uid,
-        is_admin
+        roles,
+        proxy_users,
+        teams,
     })
 }
+
+/// Returns the proxy users of the user, including the values of the
+/// LDAP attributes of the user in `proxy_user_aliases`.
+fn find_proxy_users(ldap: &mut LdapConn, proxy_user_aliases: &[ProxyUserAlias], user_dn: &str, uid: &str) -> Result<Vec<String>, Box<Error>> {
+    let mut proxy_users = authz::proxy_users(proxy_user_aliases, uid);
+
+    for alias in proxy_user_aliases {
+        if let ProxyUserAlias::LdapAttribute { ref attribute } = *alias {
+            for entry in search(ldap, user_dn, Scope::Base, "(objectClass=*)", vec![attribute.as_str()])? {
+                proxy_users.extend(attribute_values(&entry, attribute).into_iter().cloned());
+            }
+        }
+    }
+
+    proxy_users.retain(|proxy_user| proxy_user != uid);
+    proxy_users.sort();
+    proxy_users.dedup();
+    Ok(proxy_users)
+}
+
+/// Returns the teams which the user is a member of, with all of their members.
+///
+/// The members include their proxy users except for the ones from LDAP attributes.
+fn find_teams(ldap: &mut LdapConn, conf: &LDAP, teams: &[config::Team], proxy_user_aliases: &[ProxyUserAlias], user_dn: &str, uid: &str) -> Result<Vec<Team>, Box<Error>> {
+    let mut found = Vec::new();
+
+    for team in teams {
+        let mut members = team.members.clone().unwrap_or_default();
+        let group_dns = team.group_dns.clone().unwrap_or_default();
+
+        let is_member = members.iter().any(|member| member == uid)
+            || (!group_dns.is_empty() && is_member_of_any(ldap, conf, user_dn, uid, &group_dns)?);
+
+        if !is_member {
+            continue;
+        }
+
+        if !group_dns.is_empty() {
+            members.extend(member_uids(ldap, conf, &group_dns)?);
+        }
+
+        found.push(authz::team(team, members, proxy_user_aliases));
+    }
+
+    Ok(found)
+}
+
+/// Returns the DN of the user `uid`.
+///
+/// If `user_search` is configured, the DN is searched for after binding
+/// as the service account. Otherwise it is built from `user_dn`.
+fn find_user_dn(ldap: &mut LdapConn, conf: &LDAP, uid: &str) -> Result<String, AuthError> {
+    let user_search = match (conf.user_search.as_ref(), conf.user_dn.as_ref()) {
+        (Some(user_search), _) => user_search,
+        (None, Some(user_dn)) => return Ok(user_dn.replace("{}", &dn_escape(uid))),
+        (None, None) => return Err(AuthError::unavailable("either user_dn or user_search is required")),
+    };
+
+    ldap.simple_bind(user_search.bind_dn.as_str(), user_search.bind_password.as_str())
+        .and_then(|res| res.success())
+        .map_err(|err| AuthError::Unavailable(format!("failed to bind as {}: {}", user_search.bind_dn, err)))?;
+
+    let filter = user_search.filter.replace("{}", &ldap_escape(uid));
+
+    // "1.1" requests no attributes because only the DN is needed.
+    let (mut res, _) = ldap.search(user_search.base_dn.as_str(), Scope::Subtree, filter.as_str(), vec!["1.1"])
+        .and_then(|res| res.success())
+        .map_err(AuthError::unavailable)?;
+
+    match res.len() {
+        1 => Ok(SearchEntry::construct(res.remove(0)).dn),
+        0 => Err(AuthError::InvalidCredentials(format!("no user matches {} in {}", filter, user_search.base_dn))),
+        n => Err(AuthError::Unavailable(format!("{} users match {} in {}", n, filter, user_search.base_dn))),
+    }
+}
+
+/// Returns whether the user is a member of any of `group_dns`.
+fn is_member_of_any(ldap: &mut LdapConn, conf: &LDAP, user_dn: &str, uid: &str, group_dns: &[String]) -> Result<bool, Box<Error>> {
+    match conf.group_membership() {
+        GroupMembership::MemberAttribute { attribute, nested_base_dn } => {
+            let attribute = attribute.unwrap_or_else(|| "member".to_string());
+            let member = if attribute.eq_ignore_ascii_case("memberUid") { uid } else { user_dn };
+
+            match nested_base_dn {
+                Some(base_dn) => is_nested_member(ldap, &base_dn, &attribute, member, group_dns),
+                None => any_group_matches(ldap, group_dns, &format!("({}={})", attribute, ldap_escape(member))),
+            }
+        },
+        GroupMembership::MemberOf { attribute } => {
+            let attribute = attribute.unwrap_or_else(|| "memberOf".to_string());
+            let entries = search(ldap, user_dn, Scope::Base, "(objectClass=*)", vec![attribute.as_str()])?;
+
+            Ok(entries.iter()
+                .flat_map(|entry| attribute_values(entry, &attribute))
+                .any(|group_dn| contains_dn(group_dns, group_dn)))
+        },
+        GroupMembership::InChain => {
+            any_group_matches(ldap, group_dns, &format!("(member:1.2.840.113556.1.4.1941:={})", ldap_escape(user_dn)))
+        },
+    }
+}
+
+/// Returns the uids of the members of any of `group_dns`.
+fn member_uids(ldap: &mut LdapConn, conf: &LDAP, group_dns: &[String]) -> Result<Vec<String>, Box<Error>> {
+    let uid_attribute = conf.uid_attribute();
+
+    let member_of = match conf.group_membership() {
+        GroupMembership::MemberAttribute { attribute, nested_base_dn } => {
+            let attribute = attribute.unwrap_or_else(|| "member".to_string());
+            return if attribute.eq_ignore_ascii_case("memberUid") {
+                let mut uids = Vec::new();
+                for group_dn in group_dns {
+                    for entry in search(ldap, group_dn, Scope::Base, "(objectClass=*)", vec![attribute.as_str()])? {
+                        uids.extend(attribute_values(&entry, &attribute).into_iter().cloned());
+                    }
+                }
+                Ok(uids)
+            } else {
+                nested_member_uids(ldap, nested_base_dn.as_ref().map(String::as_str), &attribute, &uid_attribute, group_dns)
+            };
+        },
+        GroupMembership::MemberOf { attribute } => attribute.unwrap_or_else(|| "memberOf".to_string()),
+        GroupMembership::InChain => "memberOf:1.2.840.113556.1.4.1941:".to_string(),
+    };
+
+    let base_dn = conf.user_base_dn()
+        .ok_or_else(|| format!("no base DN of users to search for the members of {:?}", group_dns))?;
+
+    let mut uids = Vec::new();
+    for group_dn in group_dns {
+        let filter = format!("({}={})", member_of, ldap_escape(group_dn.as_str()));
+        for entry in search(ldap, &base_dn, Scope::Subtree, &filter, vec![uid_attribute.as_str()])? {
+            uids.extend(attribute_values(&entry, &uid_attribute).into_iter().cloned());
+        }
+    }
+
+    Ok(uids)
+}
+
+/// Returns the uids of the members of any of `group_dns` whose `attribute`
+/// values are the DNs of their members. If `nested_base_dn` is set, members
+/// under it without `uid_attribute` are followed as nested groups.
+fn nested_member_uids(ldap: &mut LdapConn, nested_base_dn: Option<&str>, attribute: &str, uid_attribute: &str, group_dns: &[String]) -> Result<Vec<String>, Box<Error>> {
+    let mut uids = Vec::new();
+    let mut groups = group_dns.to_vec();
+    let mut visited: HashSet<String> = group_dns.iter().map(|dn| normalize_dn(dn)).collect();
+
+    for _ in 0..MAX_NESTING_DEPTH {
+        let mut nested_groups = Vec::new();
+
+        for group_dn in &groups {
+            let member_dns: Vec<String> = search(ldap, group_dn, Scope::Base, "(objectClass=*)", vec![attribute])?
+                .iter()
+                .flat_map(|entry| attribute_values(entry, attribute).into_iter().cloned())
+                .collect();
+
+            for member_dn in member_dns {
+                if !visited.insert(normalize_dn(&member_dn)) {
+                    continue;
+                }
+
+                for entry in search(ldap, &member_dn, Scope::Base, "(objectClass=*)", vec![uid_attribute, attribute])? {
+                    let member_uids = attribute_values(&entry, uid_attribute);
+
+                    if !member_uids.is_empty() {
+                        uids.extend(member_uids.into_iter().cloned());
+                    } else if nested_base_dn.map_or(false, |base_dn| is_under(&entry.dn, base_dn)) {
+                        nested_groups.push(entry.dn);
+                    }
+                }
+            }
+        }
+
+        if nested_groups.is_empty() {
+            break;
+        }
+
+        groups = nested_groups;
+    }
+
+    Ok(uids)
+}
+
+/// Returns the values of `attribute` of `entry`.
+fn attribute_values<'a>(entry: &'a SearchEntry, attribute: &str) -> Vec<&'a String> {
+    entry.attrs.iter()
+        .filter(|&(name, _)| name.eq_ignore_ascii_case(attribute))
+        .flat_map(|(_, values)| values.iter())
+        .collect()
+}
+
+/// Returns whether any of `group_dns` matches `filter`.
+fn any_group_matches(ldap: &mut LdapConn, group_dns: &[String], filter: &str) -> Result<bool, Box<Error>> {
+    for group_dn in group_dns {
+        if !search(ldap, group_dn, Scope::Base, filter, vec!["1.1"])?.is_empty() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Returns whether `member` is a member of any of `group_dns` directly or
+/// through the groups under `base_dn` which are members of other groups.
+fn is_nested_member(ldap: &mut LdapConn, base_dn: &str, attribute: &str, member: &str, group_dns: &[String]) -> Result<bool, Box<Error>> {
+    let mut members = vec![member.to_string()];
+    let mut visited = HashSet::new();
+
+    for _ in 0..MAX_NESTING_DEPTH {
+        let filter = format!("(|{})", members.iter()
+            .map(|member| format!("({}={})", attribute, ldap_escape(member.as_str())))
+            .collect::<String>());
+
+        let groups = search(ldap, base_dn, Scope::Subtree, &filter, vec!["1.1"])?;
+
+        if groups.iter().any(|group| contains_dn(group_dns, &group.dn)) {
+            return Ok(true);
+        }
+
+        // Nested groups are members of their parent groups by their DNs.
+        members = groups.into_iter()
+            .map(|group| group.dn)
+            .filter(|dn| visited.insert(normalize_dn(dn)))
+            .collect();
+
+        if members.is_empty() {
+            break;
+        }
+    }
+
+    Ok(false)
+}
+
+/// Searches for entries. A nonexistent `base` results in no entries.
+fn search(ldap: &mut LdapConn, base: &str, scope: Scope, filter: &str, attrs: Vec<&str>) -> Result<Vec<SearchEntry>, Box<Error>> {
+    let res = ldap.search(base, scope, filter, attrs)?;
+
+    if res.1.rc == NO_SUCH_OBJECT {
+        return Ok(Vec::new());
+    }
+
+    let (entries, _) = res.success()
+        .map_err(|err| format!("failed to search {} for {}: {}", base, filter, err))?;
+
+    Ok(entries.into_iter().map(SearchEntry::construct).collect())
+}
+
+fn contains_dn(dns: &[String], dn: &str) -> bool {
+    let dn = normalize_dn(dn);
+    dns.iter().any(|d| normalize_dn(d) == dn)
+}
+
+/// Returns whether `dn` is `base_dn` or under it.
+fn is_under(dn: &str, base_dn: &str) -> bool {
+    let dn = normalize_dn(dn);
+    let base_dn = normalize_dn(base_dn);
+    dn == base_dn || dn.ends_with(&format!(",{}", base_dn))
+}
+
+/// Normalizes `dn` for comparison by lowercasing it and removing spaces
+/// around the separators.
+fn normalize_dn(dn: &str) -> String {
+    dn.split(',')
+        .map(|rdn| rdn.split('=').map(str::trim).collect::<Vec<_>>().join("="))
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_lowercase()
+}
+
+/// Connects to the LDAP server, upgrading the connection with StartTLS if
+/// `starttls` is set.
+///
+/// The connection is refused before sending any credentials if it is not
+/// encrypted and `require_tls` is set.
+fn connect(conf: &LDAP) -> Result<LdapConn, Box<Error>> {
+    if conf.require_tls.unwrap_or(false) && !conf.is_tls() {
+        return Err(From::from(format!("refused to bind to {} without TLS because require_tls is set", conf.url)));
+    }
+
+    let mut settings = LdapConnSettings::new().set_starttls(conf.starttls.unwrap_or(false));
+
+    if let Some(ref ca_file) = conf.ca_file {
+        settings = settings.set_connector(tls_connector(ca_file)?);
+    }
+
+    match LdapConn::with_settings(settings, conf.url.as_str()) {
+        Ok(ldap) => Ok(ldap),
+        Err(err) => Err(From::from(format!("failed to connect to {}: {}", conf.url, err))),
+    }
+}
+
+/// Creates a TLS connector which trusts the CA certificates in `ca_file`.
+pub fn tls_connector(ca_file: &str) -> Result<TlsConnector, Box<Error>> {
+    let mut pem = Vec::new();
+    File::open(ca_file)
+        .and_then(|mut f| f.read_to_end(&mut pem))
+        .map_err(|err| format!("failed to read {}: {}", ca_file, err))?;
+
+    let pem = String::from_utf8_lossy(&pem);
+    let mut builder = TlsConnector::builder();
+    let mut found = false;
+
+    for block in pem.split(PEM_CERTIFICATE_BEGIN).skip(1) {
+        let cert = Certificate::from_pem(format!("{}{}", PEM_CERTIFICATE_BEGIN, block).as_bytes())
+            .map_err(|err| format!("invalid CA certificate in {}: {}", ca_file, err))?;
+        builder.add_root_certificate(cert);
+        found = true;
+    }
+
+    if !found {
+        return Err(From::from(format!("no CA certificates in {}", ca_file)));
+    }
+
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_dn() {
+        let dns = vec!["CN=Admins, OU=Groups,DC=example,DC=com".to_string()];
+
+        assert!(contains_dn(&dns, "cn=admins,ou=groups,dc=example,dc=com"));
+        assert!(contains_dn(&dns, "cn = Admins ,ou=Groups, dc=example, dc=com"));
+        assert!(!contains_dn(&dns, "cn=users,ou=groups,dc=example,dc=com"));
+    }
+
+    #[test]
+    fn test_is_under() {
+        assert!(is_under("cn=data,OU=Groups,dc=example,dc=com", "ou=groups, dc=example,dc=com"));
+        assert!(is_under("ou=groups,dc=example,dc=com", "ou=groups,dc=example,dc=com"));
+        assert!(!is_under("cn=data,ou=mygroups,dc=example,dc=com", "ou=groups,dc=example,dc=com"));
+    }
+}
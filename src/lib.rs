@@ -2,11 +2,20 @@
 //! Web UI for Managing Apache Livy Sessions
 
 extern crate argparse;
+extern crate base64;
+extern crate bcrypt;
+extern crate curl;
+extern crate hyper;
 extern crate iron;
 extern crate ldap3;
+extern crate lettre;
+extern crate libc;
 extern crate livy;
+extern crate native_tls;
+extern crate openssl;
 extern crate params;
 extern crate persistent;
+extern crate regex;
 extern crate router;
 extern crate serde;
 #[macro_use]
@@ -14,18 +23,47 @@ extern crate serde_derive;
 extern crate serde_json;
 extern crate time;
 extern crate toml;
+extern crate url;
 extern crate uuid;
 
+/// Personal API tokens of users
+pub mod api_token;
+/// Audit log of logins and kills
+pub mod audit;
+/// Authentication of users
+pub mod authn;
+/// Authorization of users
+pub mod authz;
 /// Command-line arguments
 pub mod cmd_args;
 /// Configuration for Livy Manager
 pub mod config;
 /// Frontend resources
 pub mod frontend;
+/// Notifications to the owners of killed sessions and batches
+pub mod kill_notification;
 /// LDAP client
 pub mod ldap;
+/// Livy REST API resources which are not fully covered by the `livy` crate
+pub mod livy_ext;
+/// Throttle of failed logins
+pub mod login_throttle;
+/// Submission of new batches
+pub mod new_batch;
+/// Creation of new interactive sessions
+pub mod new_session;
+/// OpenID Connect client
+pub mod oidc;
+/// Authentication by the headers of an authenticating reverse proxy
+pub mod proxy_auth;
 /// HTTP server
 pub mod server;
+/// Stores of login sessions
+pub mod session_store;
+/// SPNEGO single sign-on of browsers
+pub mod spnego;
+/// HTTPS listener
+pub mod tls;
 
 #[cfg(test)]
 mod tests {
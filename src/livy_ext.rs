@@ -0,0 +1,235 @@
+use config::LivyClient;
+use livy::client::NewBatchRequest;
+use livy::http;
+use livy::http::Method::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Active interactive sessions
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Sessions {
+    pub from: Option<i64>,
+    pub total: Option<i64>,
+    pub sessions: Option<Vec<Session>>,
+}
+
+/// Session which represents an interactive shell
+///
+/// Unlike `livy::client::Session`, the kind and the state are kept as
+/// strings so that kinds such as `sql` which are unknown to the `livy`
+/// crate can be handled.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// Name of the Livy cluster, which is set by Livy Manager
+    pub cluster: Option<String>,
+    pub id: Option<i64>,
+    pub name: Option<String>,
+    pub app_id: Option<String>,
+    pub owner: Option<String>,
+    pub proxy_user: Option<String>,
+    pub kind: Option<String>,
+    pub log: Option<Vec<String>>,
+    pub state: Option<String>,
+    pub app_info: Option<HashMap<String, Option<String>>>,
+}
+
+/// New session request information
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionRequest {
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jars: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver_memory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver_cores: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executor_memory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executor_cores: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_executors: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conf: Option<HashMap<String, String>>,
+}
+
+/// Batches information
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Batches {
+    pub from: Option<i64>,
+    pub total: Option<i64>,
+    pub sessions: Option<Vec<Batch>>,
+}
+
+/// Single batch information
+///
+/// Unlike `livy::client::Batch`, this includes the owner and
+/// the proxy user of the batch.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Batch {
+    /// Name of the Livy cluster, which is set by Livy Manager
+    pub cluster: Option<String>,
+    pub id: Option<i64>,
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub proxy_user: Option<String>,
+    pub app_id: Option<String>,
+    pub app_info: Option<HashMap<String, Option<String>>>,
+    pub log: Option<Vec<String>>,
+    pub state: Option<String>,
+}
+
+/// Statements of an interactive session
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Statements {
+    pub total_statements: Option<i64>,
+    pub statements: Option<Vec<Statement>>,
+}
+
+/// Statement
+///
+/// Unlike `livy::client::Statement`, this includes the code and
+/// the progress of the statement.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Statement {
+    pub id: Option<i64>,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub output: Option<StatementOutput>,
+    pub progress: Option<f64>,
+}
+
+impl Statement {
+    /// Returns a one-line summary of the output, which is truncated
+    /// to `max_chars` characters.
+    pub fn output_summary(&self, max_chars: usize) -> Option<String> {
+        let output = match self.output {
+            Some(ref output) => output,
+            None => return None,
+        };
+
+        let summary = match (output.ename.as_ref(), output.evalue.as_ref()) {
+            (Some(ename), Some(evalue)) => format!("{}: {}", ename, evalue),
+            (Some(ename), None) => ename.clone(),
+            _ => {
+                match output.data.as_ref().and_then(|data| data.get("text/plain")) {
+                    Some(&Value::String(ref text)) => text.clone(),
+                    Some(value) => value.to_string(),
+                    None => output.status.clone().unwrap_or_default(),
+                }
+            },
+        };
+
+        let summary = summary.lines().next().unwrap_or("");
+
+        if summary.chars().count() > max_chars {
+            Some(format!("{}...", summary.chars().take(max_chars).collect::<String>()))
+        } else {
+            Some(summary.to_string())
+        }
+    }
+}
+
+/// Statement output
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatementOutput {
+    pub status: Option<String>,
+    pub execution_count: Option<i64>,
+    pub data: Option<HashMap<String, Value>>,
+    pub ename: Option<String>,
+    pub evalue: Option<String>,
+    pub traceback: Option<Vec<String>>,
+}
+
+/// Gets information of batches and returns it.
+///
+/// # HTTP Request
+/// GET /batches
+pub fn get_batches(conf: &LivyClient, from: Option<i64>, size: Option<i64>) -> Result<Batches, String> {
+    let params = http::params(vec![
+        http::param("from", from),
+        http::param("size", size),
+    ]);
+
+    get(conf, format!("/batches{}", params).as_str())
+}
+
+/// Gets information of a single batch and returns it.
+///
+/// # HTTP Request
+/// GET /batches/{batchId}
+pub fn get_batch(conf: &LivyClient, batch_id: i64) -> Result<Batch, String> {
+    get(conf, format!("/batches/{}", batch_id).as_str())
+}
+
+/// Gets information of sessions and returns it.
+///
+/// # HTTP Request
+/// GET /sessions
+pub fn get_sessions(conf: &LivyClient, from: Option<i64>, size: Option<i64>) -> Result<Sessions, String> {
+    let params = http::params(vec![
+        http::param("from", from),
+        http::param("size", size),
+    ]);
+
+    get(conf, format!("/sessions{}", params).as_str())
+}
+
+/// Gets information of a single session and returns it.
+///
+/// # HTTP Request
+/// GET /sessions/{sessionId}
+pub fn get_session(conf: &LivyClient, session_id: i64) -> Result<Session, String> {
+    get(conf, format!("/sessions/{}", session_id).as_str())
+}
+
+/// Creates a new interactive session and returns it.
+///
+/// # HTTP Request
+/// POST /sessions
+pub fn create_session(conf: &LivyClient, new_session_request: &NewSessionRequest) -> Result<Session, String> {
+    post(conf, "/sessions", new_session_request)
+}
+
+/// Creates a new batch and returns it.
+///
+/// # HTTP Request
+/// POST /batches
+pub fn create_batch(conf: &LivyClient, new_batch_request: &NewBatchRequest) -> Result<Batch, String> {
+    post(conf, "/batches", new_batch_request)
+}
+
+/// Gets statements of an interactive session and returns them.
+///
+/// # HTTP Request
+/// GET /sessions/{sessionId}/statements
+pub fn get_statements(conf: &LivyClient, session_id: i64) -> Result<Statements, String> {
+    get(conf, format!("/sessions/{}/statements", session_id).as_str())
+}
+
+fn get<T: DeserializeOwned>(conf: &LivyClient, path: &str) -> Result<T, String> {
+    http::send(GET,
+               format!("{}{}", http::remove_trailing_slash(&conf.url), path).as_str(),
+               None::<()>,
+               conf.gssnegotiate.as_ref(),
+               conf.username.as_ref().map(String::as_ref))
+}
+
+fn post<T: DeserializeOwned, U: Serialize>(conf: &LivyClient, path: &str, data: U) -> Result<T, String> {
+    http::send(POST,
+               format!("{}{}", http::remove_trailing_slash(&conf.url), path).as_str(),
+               Some(data),
+               conf.gssnegotiate.as_ref(),
+               conf.username.as_ref().map(String::as_ref))
+}
@@ -0,0 +1,319 @@
+use config;
+use hyper::header::Headers;
+use proxy_auth::Cidr;
+use std::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str;
+use std::sync::Mutex;
+
+/// Maximum number of tracked uids and IP addresses, so that attempts with
+/// random uids do not exhaust the memory
+const MAX_ENTRIES: usize = 10000;
+
+/// Failed logins of a uid or from an IP address
+#[derive(Clone, Debug, PartialEq)]
+struct Failures {
+    count: u32,
+    /// Unix time in seconds of the last failure
+    last_failed_at: i64,
+}
+
+/// Login which is counted as a failure from when it is allowed until it is
+/// released, so that concurrent attempts cannot bypass the throttle
+#[derive(Debug)]
+pub struct Attempt {
+    uid: String,
+    /// Failures of the uid and from the IP address before the attempt
+    previous: Vec<(String, Option<Failures>)>,
+}
+
+/// Throttle of logins which rejects the attempts of a uid or from an IP
+/// address for exponentially longer after each failure, and locks them
+/// out after too many failures
+///
+/// Failures are forgotten `lockout_secs` after the last one, and those of
+/// a uid also when the user logs in.
+pub struct Throttle {
+    conf: config::LoginThrottle,
+    trusted_proxies: Vec<Cidr>,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl Throttle {
+    /// Creates a new `Throttle`.
+    pub fn new(conf: config::LoginThrottle) -> Throttle {
+        Throttle {
+            // The CIDRs have been validated with the configuration.
+            trusted_proxies: conf.trusted_proxies.iter().flat_map(|proxies| proxies.iter())
+                .filter_map(|proxy| Cidr::parse(proxy).ok()).collect(),
+            conf,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the address of the client, which is the last address in
+    /// `X-Forwarded-For` not of a trusted proxy if the request is from one.
+    pub fn client_ip(&self, remote_addr: IpAddr, headers: &Headers) -> IpAddr {
+        let is_trusted = |addr: &IpAddr| self.trusted_proxies.iter().any(|cidr| cidr.contains(addr));
+
+        if !is_trusted(&remote_addr) {
+            return remote_addr;
+        }
+
+        let forwarded: Vec<IpAddr> = headers.get_raw("X-Forwarded-For").into_iter()
+            .flat_map(|values| values.iter())
+            .filter_map(|value| str::from_utf8(value).ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|addr| addr.trim().parse().ok())
+            .collect();
+
+        forwarded.into_iter().rev().find(|addr| !is_trusted(addr)).unwrap_or(remote_addr)
+    }
+
+    /// Starts a login of `uid` from `ip`, which is counted as a failure
+    /// unless it is released. Returns the seconds until the next login is
+    /// allowed if it is not allowed now.
+    ///
+    /// The check and the count are atomic, so parallel attempts wait for
+    /// each other's backoff.
+    pub fn attempt(&self, uid: &str, ip: &IpAddr, now: i64) -> Result<Attempt, i64> {
+        let mut failures = self.failures.lock().unwrap();
+        let lockout_secs = self.conf.lockout_secs();
+
+        let retry_after = self.retry_after(&failures, uid, ip, now);
+        if retry_after > 0 {
+            return Err(retry_after);
+        }
+
+        if failures.len() >= MAX_ENTRIES {
+            evict(&mut failures, now - lockout_secs);
+        }
+
+        let mut previous = Vec::new();
+
+        for key in vec![uid_key(uid), ip_key(ip)] {
+            previous.push((key.clone(), failures.get(&key).cloned()));
+
+            let entry = failures.entry(key).or_insert(Failures { count: 0, last_failed_at: now });
+            if entry.last_failed_at + lockout_secs <= now {
+                entry.count = 0;
+            }
+            entry.count = entry.count.saturating_add(1);
+            entry.last_failed_at = now;
+        }
+
+        Ok(Attempt { uid: uid.to_string(), previous })
+    }
+
+    /// Forgets the failures of the uid of `attempt` after the user logs in.
+    ///
+    /// The failures from the IP address are kept, so that an attacker who
+    /// knows a password cannot reset them.
+    pub fn succeed(&self, attempt: Attempt) {
+        self.failures.lock().unwrap().remove(&uid_key(&attempt.uid));
+        self.release(attempt);
+    }
+
+    /// Stops counting `attempt` as a failure, such as when the password
+    /// could not be verified because the LDAP server is down.
+    pub fn release(&self, attempt: Attempt) {
+        let mut failures = self.failures.lock().unwrap();
+
+        for (key, previous) in attempt.previous {
+            let restored = match failures.get_mut(&key) {
+                Some(entry) => {
+                    entry.count = entry.count.saturating_sub(1);
+                    // The time of the previous failure is restored unless
+                    // other attempts have been counted since.
+                    match previous {
+                        Some(ref previous) if previous.count == entry.count => entry.last_failed_at = previous.last_failed_at,
+                        _ => (),
+                    }
+                    entry.count > 0
+                },
+                None => continue,
+            };
+
+            if !restored {
+                failures.remove(&key);
+            }
+        }
+    }
+
+    /// Returns the seconds until the next login of `uid` from `ip` is
+    /// allowed, which is not positive if it is allowed now.
+    fn retry_after(&self, failures: &HashMap<String, Failures>, uid: &str, ip: &IpAddr, now: i64) -> i64 {
+        cmp::max(
+            failures.get(&uid_key(uid)).map_or(0, |f| self.blocked_until(f, self.conf.max_failures()) - now),
+            failures.get(&ip_key(ip)).map_or(0, |f| self.blocked_until(f, self.conf.max_failures_per_ip()) - now),
+        )
+    }
+
+    /// Returns the Unix time in seconds until which logins are rejected
+    /// after `failures`.
+    fn blocked_until(&self, failures: &Failures, max_failures: u32) -> i64 {
+        let lockout_secs = self.conf.lockout_secs();
+
+        if failures.count >= max_failures {
+            return failures.last_failed_at + lockout_secs;
+        }
+
+        // 2^(count - 1) times the base, which is capped at the lockout
+        let exponent = cmp::min(failures.count.saturating_sub(1), 30);
+        let delay = self.conf.backoff_base_secs().saturating_mul(1 << exponent);
+        failures.last_failed_at + cmp::min(delay, lockout_secs)
+    }
+}
+
+/// Forgets the failures which have expired by `expired_at`, and then the
+/// oldest ones until a tenth of `MAX_ENTRIES` is free, so that the map is
+/// not scanned again on every attempt.
+fn evict(failures: &mut HashMap<String, Failures>, expired_at: i64) {
+    failures.retain(|_, f| f.last_failed_at > expired_at);
+
+    let max_len = MAX_ENTRIES - MAX_ENTRIES / 10;
+    if failures.len() <= max_len {
+        return;
+    }
+
+    let mut oldest: Vec<(i64, String)> = failures.iter().map(|(key, f)| (f.last_failed_at, key.clone())).collect();
+    oldest.sort();
+
+    let excess = failures.len() - max_len;
+    for (_, key) in oldest.into_iter().take(excess) {
+        failures.remove(&key);
+    }
+}
+
+/// Key of the failures of a uid, which is case-insensitive as in most
+/// directories
+fn uid_key(uid: &str) -> String {
+    format!("uid:{}", uid.to_lowercase())
+}
+
+fn ip_key(ip: &IpAddr) -> String {
+    format!("ip:{}", ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> Throttle {
+        Throttle::new(config::LoginThrottle {
+            max_failures: Some(3),
+            max_failures_per_ip: Some(5),
+            backoff_base_secs: Some(2),
+            lockout_secs: Some(60),
+            trusted_proxies: Some(vec!["10.0.0.1".to_string()]),
+        })
+    }
+
+    fn retry_after(throttle: &Throttle, uid: &str, ip: &IpAddr, now: i64) -> Option<i64> {
+        let retry_after = throttle.retry_after(&throttle.failures.lock().unwrap(), uid, ip, now);
+        if retry_after > 0 { Some(retry_after) } else { None }
+    }
+
+    /// Counts a login with wrong credentials.
+    fn fail(throttle: &Throttle, uid: &str, ip: &IpAddr, now: i64) {
+        throttle.attempt(uid, ip, now).unwrap();
+    }
+
+    #[test]
+    fn test_throttle() {
+        let throttle = throttle();
+        let ip = "192.168.0.1".parse().unwrap();
+
+        assert_eq!(None, retry_after(&throttle, "alice", &ip, 0));
+
+        // Exponential backoff
+        fail(&throttle, "alice", &ip, 0);
+        assert_eq!(Some(2), retry_after(&throttle, "Alice", &ip, 0));
+        assert_eq!(None, retry_after(&throttle, "alice", &ip, 2));
+        fail(&throttle, "alice", &ip, 2);
+        assert_eq!(Some(4), retry_after(&throttle, "alice", &ip, 2));
+
+        // Lockout of the uid, but not of the other uids from another address
+        fail(&throttle, "alice", &ip, 6);
+        assert_eq!(Some(60), retry_after(&throttle, "alice", &ip, 6));
+        assert_eq!(Some(60), retry_after(&throttle, "alice", &"192.168.0.2".parse().unwrap(), 6));
+        assert_eq!(None, retry_after(&throttle, "bob", &"192.168.0.2".parse().unwrap(), 6));
+        assert_eq!(None, retry_after(&throttle, "alice", &ip, 66));
+
+        // The failures are forgotten after the lockout.
+        fail(&throttle, "alice", &ip, 66);
+        assert_eq!(Some(2), retry_after(&throttle, "alice", &ip, 66));
+
+        // A login forgets the failures of the uid only.
+        let attempt = throttle.attempt("alice", &ip, 68).unwrap();
+        throttle.succeed(attempt);
+        assert_eq!(None, retry_after(&throttle, "alice", &"192.168.0.2".parse().unwrap(), 68));
+        assert_eq!(Some(&Failures { count: 1, last_failed_at: 66 }), throttle.failures.lock().unwrap().get(&ip_key(&ip)));
+    }
+
+    #[test]
+    fn test_concurrent_attempts() {
+        let throttle = throttle();
+        let ip = "192.168.0.1".parse().unwrap();
+
+        // The second attempt is throttled before the first one has failed,
+        // and so are the attempts of other uids from the address.
+        let attempt = throttle.attempt("alice", &ip, 0).unwrap();
+        assert_eq!(Err(2), throttle.attempt("alice", &ip, 0).map(|_| ()));
+        assert_eq!(Err(1), throttle.attempt("bob", &ip, 1).map(|_| ()));
+
+        // An attempt whose password could not be verified is not counted.
+        throttle.release(attempt);
+        assert_eq!(None, retry_after(&throttle, "alice", &ip, 0));
+        assert!(throttle.failures.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_throttle_per_ip() {
+        let throttle = throttle();
+        let ip = "192.168.0.1".parse().unwrap();
+
+        // Each attempt waits for the backoff of the address.
+        for &(uid, now) in [("a", 0), ("b", 2), ("c", 6), ("d", 14), ("e", 30)].iter() {
+            fail(&throttle, uid, &ip, now);
+        }
+
+        assert_eq!(Some(60), retry_after(&throttle, "f", &ip, 30));
+        assert_eq!(None, retry_after(&throttle, "f", &"192.168.0.2".parse().unwrap(), 30));
+    }
+
+    #[test]
+    fn test_evict() {
+        let throttle = throttle();
+        let ip = "192.168.0.1".parse().unwrap();
+
+        {
+            let mut failures = throttle.failures.lock().unwrap();
+            for i in 0..MAX_ENTRIES {
+                let last_failed_at = if i == 0 { 0 } else if i < 1000 { 50 } else { 100 };
+                failures.insert(uid_key(&format!("user{}", i)), Failures { count: 1, last_failed_at });
+            }
+        }
+
+        // The expired failures are forgotten first, and then the oldest ones.
+        throttle.attempt("alice", &ip, 100).unwrap();
+        let failures = throttle.failures.lock().unwrap();
+        assert_eq!(MAX_ENTRIES - MAX_ENTRIES / 10 + 2, failures.len());
+        assert!(!failures.contains_key(&uid_key("user0")));
+        assert!(!failures.contains_key(&uid_key("user999")));
+        assert!(failures.contains_key(&uid_key("user1000")));
+        assert!(failures.contains_key(&uid_key("alice")));
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let throttle = throttle();
+        let mut headers = Headers::new();
+        headers.set_raw("X-Forwarded-For", vec![b"1.1.1.1, 2.2.2.2, 10.0.0.1".to_vec()]);
+
+        assert_eq!("2.2.2.2".parse::<IpAddr>().unwrap(), throttle.client_ip("10.0.0.1".parse().unwrap(), &headers));
+        assert_eq!("3.3.3.3".parse::<IpAddr>().unwrap(), throttle.client_ip("3.3.3.3".parse().unwrap(), &headers));
+        assert_eq!("10.0.0.1".parse::<IpAddr>().unwrap(), throttle.client_ip("10.0.0.1".parse().unwrap(), &Headers::new()));
+    }
+}
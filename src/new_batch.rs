@@ -0,0 +1,198 @@
+use authz::{self, Action};
+use config::BatchTemplate;
+use livy::client::NewBatchRequest;
+use server::UserSession;
+use std::collections::HashMap;
+
+/// New batch form sent from the web UI
+#[derive(Debug, Deserialize)]
+pub struct NewBatchForm {
+    pub template: String,
+    pub parameters: Option<HashMap<String, String>>,
+    pub proxy_user: Option<String>,
+}
+
+/// Validates `form` against the batch job template it refers to and
+/// builds a Livy new batch request from them.
+///
+/// `user_session` is `None` if the authentication is disabled. Non-admin
+/// users always submit batches as themselves.
+pub fn build_request(form: NewBatchForm, templates: &[BatchTemplate], user_session: Option<&UserSession>) -> Result<NewBatchRequest, String> {
+    let template = match templates.iter().find(|template| template.name == form.template) {
+        Some(template) => template,
+        None => return Err(format!("unknown template: {}", form.template)),
+    };
+
+    let values = parameter_values(template, form.parameters.unwrap_or_default())?;
+
+    let proxy_user = match user_session {
+        Some(user_session) if !authz::is_allowed(true, Some(user_session), Action::CreateUnrestricted, || None) => {
+            Some(user_session.uid.clone())
+        },
+        _ => form.proxy_user.and_then(|proxy_user| {
+            let proxy_user = proxy_user.trim();
+            if proxy_user.is_empty() {
+                None
+            } else {
+                Some(proxy_user.to_string())
+            }
+        }),
+    };
+
+    let substitute_all = |items: &Option<Vec<String>>| {
+        items.as_ref().map(|items| items.iter().map(|item| substitute(item, &values)).collect())
+    };
+
+    Ok(NewBatchRequest {
+        file: substitute(&template.file, &values),
+        proxy_user,
+        class_name: template.class_name.as_ref().map(|class_name| substitute(class_name, &values)),
+        args: substitute_all(&template.args),
+        jars: template.jars.clone(),
+        py_files: substitute_all(&template.py_files),
+        files: None,
+        driver_memory: template.driver_memory.clone(),
+        driver_cores: template.driver_cores,
+        executor_memory: template.executor_memory.clone(),
+        executor_cores: template.executor_cores,
+        num_executors: template.num_executors,
+        archives: None,
+        queue: template.queue.clone(),
+        name: Some(substitute(&template.name, &values)),
+        conf: template.conf.as_ref().map(|conf| {
+            conf.iter().map(|(key, value)| (key.clone(), substitute(value, &values))).collect()
+        }),
+    })
+}
+
+/// Resolves the value of every parameter of `template` from `given`,
+/// falling back to the default values.
+fn parameter_values(template: &BatchTemplate, mut given: HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+
+    for parameter in template.parameters.as_ref().map(Vec::as_slice).unwrap_or(&[]) {
+        let value = match given.remove(&parameter.name) {
+            Some(ref value) if !value.trim().is_empty() => value.trim().to_string(),
+            _ => match parameter.default {
+                Some(ref default) => default.clone(),
+                None => return Err(format!("parameter {} is required", parameter.name)),
+            },
+        };
+
+        if let Some(ref choices) = parameter.choices {
+            if !choices.contains(&value) {
+                return Err(format!("invalid value of parameter {}: {}", parameter.name, value));
+            }
+        }
+
+        values.insert(parameter.name.clone(), value);
+    }
+
+    if let Some(name) = given.keys().next() {
+        return Err(format!("unknown parameter: {}", name));
+    }
+
+    Ok(values)
+}
+
+/// Replaces `{name}` in `s` with the value of the parameter `name`.
+/// Substituted values are not expanded again.
+pub fn substitute(s: &str, values: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        match rest.find('}').and_then(|end| values.get(&rest[1..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                result.push_str(value);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use authz::Role;
+    use config::BatchTemplateParameter;
+
+    #[test]
+    fn test_substitute() {
+        let mut values = HashMap::new();
+        values.insert("date".to_string(), "2018-01-01".to_string());
+        values.insert("table".to_string(), "{date}".to_string());
+
+        assert_eq!("--date=2018-01-01", substitute("--date={date}", &values));
+        assert_eq!("{date}", substitute("{table}", &values));
+        assert_eq!("{unknown} {", substitute("{unknown} {", &values));
+    }
+
+    #[test]
+    fn test_build_request() {
+        let templates = vec![BatchTemplate {
+            name: "daily_etl".to_string(),
+            description: None,
+            file: "hdfs:///jobs/etl.jar".to_string(),
+            class_name: None,
+            args: Some(vec!["--date".to_string(), "{date}".to_string(), "--table".to_string(), "{table}".to_string()]),
+            py_files: None,
+            jars: None,
+            driver_memory: None,
+            driver_cores: None,
+            executor_memory: None,
+            executor_cores: None,
+            num_executors: None,
+            queue: None,
+            conf: None,
+            parameters: Some(vec![
+                BatchTemplateParameter { name: "date".to_string(), description: None, default: None, choices: None },
+                BatchTemplateParameter {
+                    name: "table".to_string(),
+                    description: None,
+                    default: Some("events".to_string()),
+                    choices: Some(vec!["events".to_string(), "users".to_string()]),
+                },
+            ]),
+        }];
+        let user_session = |roles: Vec<Role>| UserSession {
+            uid: "alice".to_string(),
+            roles,
+            proxy_users: Vec::new(),
+            teams: Vec::new(),
+        };
+        let form = |parameters: &[(&str, &str)]| NewBatchForm {
+            template: "daily_etl".to_string(),
+            parameters: Some(parameters.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect()),
+            proxy_user: Some("bob".to_string()),
+        };
+
+        // Non-admin users submit batches as themselves.
+        let user = user_session(vec![Role::User]);
+        let request = build_request(form(&[("date", "2018-01-01")]), &templates, Some(&user)).unwrap();
+        assert_eq!(Some("alice".to_string()), request.proxy_user);
+        assert_eq!(Some(vec!["--date".to_string(), "2018-01-01".to_string(), "--table".to_string(), "events".to_string()]), request.args);
+
+        let admin = user_session(vec![Role::Admin]);
+        let request = build_request(form(&[("date", "2018-01-01")]), &templates, Some(&admin)).unwrap();
+        assert_eq!(Some("bob".to_string()), request.proxy_user);
+        let request = build_request(form(&[("date", "2018-01-01")]), &templates, None).unwrap();
+        assert_eq!(Some("bob".to_string()), request.proxy_user);
+
+        // Unknown and missing parameters, values out of the choices and unknown templates are rejected.
+        assert!(build_request(form(&[("date", "2018-01-01"), ("queue", "prod")]), &templates, Some(&user)).is_err());
+        assert!(build_request(form(&[]), &templates, Some(&user)).is_err());
+        assert!(build_request(form(&[("date", "2018-01-01"), ("table", "secrets")]), &templates, Some(&user)).is_err());
+        assert!(build_request(NewBatchForm { template: "unknown".to_string(), ..form(&[("date", "2018-01-01")]) }, &templates, Some(&user)).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use authz::{self, Action};
+use config::ResourceLimits;
+use livy_ext::NewSessionRequest;
+use server::UserSession;
+use std::collections::HashMap;
+
+/// Session kinds which can be created from the web UI
+pub const KINDS: [&'static str; 4] = ["spark", "pyspark", "sparkr", "sql"];
+
+/// Spark property of the upper limit of the executors added by dynamic allocation
+const MAX_EXECUTORS_CONF_KEY: &'static str = "spark.dynamicAllocation.maxExecutors";
+
+/// New session form sent from the web UI
+#[derive(Debug, Deserialize)]
+pub struct NewSessionForm {
+    pub kind: String,
+    pub name: Option<String>,
+    pub proxy_user: Option<String>,
+    pub driver_memory: Option<String>,
+    pub driver_cores: Option<i64>,
+    pub executor_memory: Option<String>,
+    pub executor_cores: Option<i64>,
+    pub num_executors: Option<i64>,
+    pub queue: Option<String>,
+    pub jars: Option<Vec<String>>,
+    pub conf: Option<HashMap<String, String>>,
+}
+
+/// Resources requested for a Spark application
+pub struct Resources<'a> {
+    pub driver_memory: Option<&'a str>,
+    pub driver_cores: Option<i64>,
+    pub executor_memory: Option<&'a str>,
+    pub executor_cores: Option<i64>,
+    pub num_executors: Option<i64>,
+    pub queue: Option<&'a str>,
+    pub conf: Option<&'a HashMap<String, String>>,
+}
+
+/// Validates `form` and builds a Livy new session request from it.
+///
+/// `user_session` is `None` if the authentication is disabled. Non-admin
+/// users always create sessions as themselves and their requests are
+/// checked against `limits`.
+pub fn build_request(form: NewSessionForm, user_session: Option<&UserSession>, limits: Option<&ResourceLimits>) -> Result<NewSessionRequest, String> {
+    if !KINDS.contains(&form.kind.as_str()) {
+        return Err(format!("invalid kind: {}", form.kind));
+    }
+
+    let restricted = user_session.is_some() &&
+        !authz::is_allowed(true, user_session, Action::CreateUnrestricted, || None);
+
+    let proxy_user = match user_session {
+        Some(user_session) if restricted => Some(user_session.uid.clone()),
+        _ => non_empty(form.proxy_user),
+    };
+
+    let mut request = NewSessionRequest {
+        kind: form.kind,
+        proxy_user,
+        jars: form.jars.map(non_empty_items).and_then(|jars| if jars.is_empty() { None } else { Some(jars) }),
+        driver_memory: non_empty(form.driver_memory),
+        driver_cores: form.driver_cores,
+        executor_memory: non_empty(form.executor_memory),
+        executor_cores: form.executor_cores,
+        num_executors: form.num_executors,
+        queue: non_empty(form.queue),
+        name: non_empty(form.name),
+        conf: form.conf.and_then(|conf| if conf.is_empty() { None } else { Some(conf) }),
+    };
+
+    if let (true, Some(limits)) = (restricted, limits) {
+        check_limits(limits, &Resources {
+            driver_memory: request.driver_memory.as_ref().map(String::as_str),
+            driver_cores: request.driver_cores,
+            executor_memory: request.executor_memory.as_ref().map(String::as_str),
+            executor_cores: request.executor_cores,
+            num_executors: request.num_executors,
+            queue: request.queue.as_ref().map(String::as_str),
+            conf: request.conf.as_ref(),
+        })?;
+
+        // Dynamic allocation enabled by the defaults of the cluster would
+        // otherwise add executors beyond the limit.
+        if let Some(num_executors) = limits.num_executors {
+            request.conf.get_or_insert_with(HashMap::new)
+                .insert(MAX_EXECUTORS_CONF_KEY.to_string(), num_executors.to_string());
+        }
+    }
+
+    Ok(request)
+}
+
+/// Checks that `resources` do not exceed `limits`.
+pub fn check_limits(limits: &ResourceLimits, resources: &Resources) -> Result<(), String> {
+    check_memory("driver_memory", limits.driver_memory.as_ref(), resources.driver_memory)?;
+    check_count("driver_cores", limits.driver_cores, resources.driver_cores)?;
+    check_memory("executor_memory", limits.executor_memory.as_ref(), resources.executor_memory)?;
+    check_count("executor_cores", limits.executor_cores, resources.executor_cores)?;
+    check_count("num_executors", limits.num_executors, resources.num_executors)?;
+
+    if let (Some(queues), Some(queue)) = (limits.queues.as_ref(), resources.queue) {
+        if !queues.iter().any(|q| q == queue) {
+            return Err(format!("queue {} is not allowed", queue));
+        }
+    }
+
+    // Only the allowed properties can be set, because the others such as
+    // spark.executor.memoryOverhead could bypass the limits.
+    if let Some(conf) = resources.conf {
+        if let Some(key) = conf.keys().find(|key| !limits.allows_conf_key(key)) {
+            return Err(format!("{} cannot be set in conf", key));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_memory(name: &str, limit: Option<&String>, value: Option<&str>) -> Result<(), String> {
+    let (limit, value) = match (limit, value) {
+        (Some(limit), Some(value)) => (limit, value),
+        _ => return Ok(()),
+    };
+
+    let limit_bytes = match parse_memory(limit) {
+        Some(limit_bytes) => limit_bytes,
+        None => return Err(format!("invalid {} limit: {}", name, limit)),
+    };
+
+    match parse_memory(value) {
+        Some(bytes) if bytes <= limit_bytes => Ok(()),
+        Some(_) => Err(format!("{} must be less than or equal to {}", name, limit)),
+        None => Err(format!("invalid {}: {}", name, value)),
+    }
+}
+
+fn check_count(name: &str, limit: Option<i64>, value: Option<i64>) -> Result<(), String> {
+    match (limit, value) {
+        (_, Some(value)) if value < 1 => Err(format!("{} must be positive", name)),
+        (Some(limit), Some(value)) if value > limit => Err(format!("{} must be less than or equal to {}", name, limit)),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a Spark memory string such as `512m` or `4g` and returns
+/// the number of bytes. A number without a unit is treated as MiB.
+pub fn parse_memory(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+
+    let (number, unit) = match s.find(|c: char| !c.is_digit(10)) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (&s[..], ""),
+    };
+
+    let multiplier: u64 = match unit {
+        "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "" | "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        "t" | "tb" => 1 << 40,
+        "p" | "pb" => 1 << 50,
+        _ => return None,
+    };
+
+    match number.parse::<u64>() {
+        Ok(number) => number.checked_mul(multiplier),
+        Err(_) => None,
+    }
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    match s {
+        Some(s) => {
+            let s = s.trim();
+            if s.is_empty() {
+                None
+            } else {
+                Some(s.to_string())
+            }
+        },
+        None => None,
+    }
+}
+
+fn non_empty_items(items: Vec<String>) -> Vec<String> {
+    items.into_iter().filter_map(|item| non_empty(Some(item))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use authz::Role;
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(Some(512 << 20), parse_memory("512m"));
+        assert_eq!(Some(4 << 30), parse_memory("4g"));
+        assert_eq!(Some(4 << 30), parse_memory("4GB"));
+        assert_eq!(Some(1024 << 20), parse_memory("1024"));
+        assert_eq!(None, parse_memory("4x"));
+        assert_eq!(None, parse_memory("g"));
+    }
+
+    #[test]
+    fn test_build_request() {
+        let limits = ResourceLimits {
+            driver_memory: Some("2g".to_string()),
+            driver_cores: None,
+            executor_memory: None,
+            executor_cores: None,
+            num_executors: Some(4),
+            queues: None,
+            allowed_conf_keys: None,
+        };
+        let user_session = UserSession {
+            uid: "alice".to_string(),
+            roles: vec![Role::User],
+            proxy_users: Vec::new(),
+            teams: Vec::new(),
+        };
+        let form = || NewSessionForm {
+            kind: "pyspark".to_string(),
+            name: None,
+            proxy_user: Some("bob".to_string()),
+            driver_memory: Some("1g".to_string()),
+            driver_cores: None,
+            executor_memory: None,
+            executor_cores: None,
+            num_executors: Some(8),
+            queue: None,
+            jars: None,
+            conf: None,
+        };
+
+        assert!(build_request(form(), Some(&user_session), Some(&limits)).is_err());
+
+        let request = build_request(NewSessionForm { num_executors: Some(2), ..form() }, Some(&user_session), Some(&limits)).unwrap();
+        assert_eq!(Some("alice".to_string()), request.proxy_user);
+        assert_eq!(Some(&"4".to_string()), request.conf.as_ref().and_then(|conf| conf.get(MAX_EXECUTORS_CONF_KEY)));
+
+        let conf = |key: &str| {
+            let mut conf = HashMap::new();
+            conf.insert(key.to_string(), "1".to_string());
+            NewSessionForm { num_executors: Some(2), conf: Some(conf), ..form() }
+        };
+        assert!(build_request(conf("spark.sql.shuffle.partitions"), Some(&user_session), Some(&limits)).is_ok());
+        for key in &["spark.executor.memoryOverhead", "spark.dynamicAllocation.minExecutors", "spark.yarn.am.cores"] {
+            assert!(build_request(conf(key), Some(&user_session), Some(&limits)).is_err());
+        }
+
+        let admin = UserSession {
+            uid: "carol".to_string(),
+            roles: vec![Role::Admin],
+            proxy_users: Vec::new(),
+            teams: Vec::new(),
+        };
+        let request = build_request(form(), Some(&admin), Some(&limits)).unwrap();
+        assert_eq!(Some("bob".to_string()), request.proxy_user);
+    }
+}
@@ -0,0 +1,461 @@
+use authz;
+use base64;
+use config::{self, Config, ProxyUserAlias, OIDC};
+use curl::easy::{Easy2, Handler, List, WriteError};
+use livy::http::remove_trailing_slash;
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::rsa::Rsa;
+use openssl::sha::sha256;
+use openssl::sign::Verifier;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use server::UserSession;
+use std::sync::RwLock;
+use std::time::Duration;
+use time;
+use url::Url;
+use url::form_urlencoded;
+
+/// Seconds of the clock skew between Livy Manager and the OpenID provider
+/// which is tolerated when the expiry of an ID token is checked
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// Timeout of the requests to the OpenID provider
+const HTTP_TIMEOUT_SECS: u64 = 10;
+
+/// Number of random bytes of the state, the nonce and the PKCE code verifier
+const RANDOM_TOKEN_BYTES: usize = 32;
+
+/// Provider metadata in the discovery document
+#[derive(Clone, Debug, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// JSON Web Key Set of the provider
+#[derive(Clone, Debug, Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+/// JSON Web Key, of which only RSA keys are used
+#[derive(Clone, Debug, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    #[serde(rename = "use")]
+    use_: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+/// JOSE header of an ID token
+#[derive(Debug, Deserialize)]
+struct JoseHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Response of the token endpoint
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// Login in progress, which is kept in a cookie of the browser until the
+/// OpenID provider redirects the user back to Livy Manager
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingLogin {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+impl PendingLogin {
+    /// Creates a new `PendingLogin` with random values.
+    pub fn new() -> Result<PendingLogin, String> {
+        Ok(PendingLogin {
+            state: random_token()?,
+            nonce: random_token()?,
+            code_verifier: random_token()?,
+        })
+    }
+
+    /// Encodes the login as a cookie value.
+    pub fn encode(&self) -> String {
+        format!("{}.{}.{}", self.state, self.nonce, self.code_verifier)
+    }
+
+    /// Decodes a cookie value encoded by `encode`.
+    pub fn decode(value: &str) -> Option<PendingLogin> {
+        let fields: Vec<&str> = value.split('.').collect();
+
+        match fields.as_slice() {
+            &[state, nonce, code_verifier] if fields.iter().all(|field| !field.is_empty()) => Some(PendingLogin {
+                state: state.to_string(),
+                nonce: nonce.to_string(),
+                code_verifier: code_verifier.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the PKCE code challenge by the `S256` method.
+    fn code_challenge(&self) -> String {
+        base64::encode_config(&sha256(self.code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+    }
+}
+
+/// OpenID Connect client which logs users in by the authorization code flow
+pub struct Client {
+    conf: OIDC,
+    teams: Vec<config::Team>,
+    proxy_user_aliases: Vec<ProxyUserAlias>,
+    /// Metadata and keys of the provider, which are fetched on the first login
+    /// so that Livy Manager can start while the provider is unavailable
+    provider: RwLock<Option<(Metadata, Jwks)>>,
+}
+
+impl Client {
+    /// Creates a new `Client` if OpenID Connect is configured.
+    pub fn new(conf: &Config) -> Option<Client> {
+        conf.oidc.as_ref().map(|oidc| Client {
+            conf: oidc.clone(),
+            teams: conf.teams(),
+            proxy_user_aliases: conf.proxy_user_aliases(),
+            provider: RwLock::new(None),
+        })
+    }
+
+    /// Returns the URL of the authorization endpoint to which the user is
+    /// redirected to log in.
+    pub fn authorization_url(&self, pending: &PendingLogin) -> Result<String, String> {
+        let (metadata, _) = self.provider(false)?;
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|err| format!("invalid authorization_endpoint: {}: {}", metadata.authorization_endpoint, err))?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.conf.client_id)
+            .append_pair("redirect_uri", &self.conf.redirect_uri)
+            .append_pair("scope", &self.conf.scopes().join(" "))
+            .append_pair("state", &pending.state)
+            .append_pair("nonce", &pending.nonce)
+            .append_pair("code_challenge", &pending.code_challenge())
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into_string())
+    }
+
+    /// Exchanges the authorization code for an ID token and returns the
+    /// login session of the user of the ID token.
+    pub fn authenticate(&self, pending: &PendingLogin, code: &str) -> Result<UserSession, String> {
+        let (metadata, jwks) = self.provider(false)?;
+
+        let form = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.conf.redirect_uri)
+            .append_pair("client_id", &self.conf.client_id)
+            .append_pair("code_verifier", &pending.code_verifier)
+            .finish();
+
+        let token: TokenResponse = self.send(&metadata.token_endpoint, Some(form))?;
+        let id_token = token.id_token
+            .ok_or_else(|| format!("no id_token in the response of {}", metadata.token_endpoint))?;
+
+        let header = decode_header(&id_token)?;
+
+        // The keys are fetched again if the key is not found, which happens
+        // when the provider has rotated its keys.
+        let key = match find_key(&jwks, header.kid.as_ref().map(String::as_str)) {
+            Some(key) => key,
+            None => {
+                let (_, jwks) = self.provider(true)?;
+                find_key(&jwks, header.kid.as_ref().map(String::as_str))
+                    .ok_or_else(|| format!("no key of the ID token in {}: {:?}", metadata.jwks_uri, header.kid))?
+            },
+        };
+
+        let claims = verify_id_token(&id_token, &header, &key)?;
+        validate_claims(&claims, &metadata.issuer, &self.conf.client_id, &pending.nonce, time::get_time().sec)?;
+
+        let uid_claim = self.conf.uid_claim();
+        let uid = match claims.get(&uid_claim) {
+            Some(&Value::String(ref uid)) if !uid.is_empty() => uid.clone(),
+            _ => return Err(format!("no {} claim in the ID token", uid_claim)),
+        };
+
+        let groups = match claims.get(&self.conf.groups_claim()) {
+            Some(&Value::Array(ref groups)) => groups.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+            Some(&Value::String(ref group)) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+
+        Ok(UserSession {
+            roles: self.conf.roles(&groups),
+            proxy_users: authz::proxy_users(&self.proxy_user_aliases, &uid),
+            teams: authz::static_teams(&self.teams, &self.proxy_user_aliases, &uid),
+            uid,
+        })
+    }
+
+    /// Returns the metadata and the keys of the provider, which are fetched
+    /// if they have not been fetched yet or `refresh` is true.
+    fn provider(&self, refresh: bool) -> Result<(Metadata, Jwks), String> {
+        if !refresh {
+            if let Some(ref provider) = *self.provider.read().unwrap() {
+                return Ok(provider.clone());
+            }
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", remove_trailing_slash(&self.conf.issuer));
+        let metadata: Metadata = self.send(&url, None)?;
+
+        // The issuer in the metadata has to be identical to the configured one.
+        if remove_trailing_slash(&metadata.issuer) != remove_trailing_slash(&self.conf.issuer) {
+            return Err(format!("issuer in {} does not match oidc.issuer: {}", url, metadata.issuer));
+        }
+
+        let jwks: Jwks = self.send(&metadata.jwks_uri, None)?;
+
+        *self.provider.write().unwrap() = Some((metadata.clone(), jwks.clone()));
+
+        Ok((metadata, jwks))
+    }
+
+    /// Sends a GET request, or a POST request of `form` with the client
+    /// credentials, to the provider and deserializes the JSON response.
+    fn send<T: DeserializeOwned>(&self, url: &str, form: Option<String>) -> Result<T, String> {
+        let mut easy = Easy2::new(Collector(Vec::new()));
+
+        let res = (|| {
+            easy.url(url)?;
+            easy.timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))?;
+
+            if let Some(ref ca_file) = self.conf.ca_file {
+                easy.cainfo(ca_file)?;
+            }
+
+            let mut headers = List::new();
+            headers.append("Accept: application/json")?;
+
+            if let Some(ref form) = form {
+                headers.append("Content-Type: application/x-www-form-urlencoded")?;
+                easy.post(true)?;
+                easy.post_fields_copy(form.as_bytes())?;
+
+                // client_secret_basic
+                if let Some(ref client_secret) = self.conf.client_secret {
+                    easy.username(&form_urlencoded::byte_serialize(self.conf.client_id.as_bytes()).collect::<String>())?;
+                    easy.password(&form_urlencoded::byte_serialize(client_secret.as_bytes()).collect::<String>())?;
+                }
+            }
+
+            easy.http_headers(headers)?;
+            easy.perform()?;
+            easy.response_code()
+        })();
+
+        let body = String::from_utf8_lossy(&easy.get_ref().0).into_owned();
+
+        match res {
+            Ok(200) => serde_json::from_str(&body).map_err(|err| format!("invalid response of {}: {}", url, err)),
+            Ok(code) => Err(format!("{} returned {}: {}", url, code, body)),
+            Err(err) => Err(format!("failed to request {}: {}", url, err)),
+        }
+    }
+}
+
+struct Collector(Vec<u8>);
+
+impl Handler for Collector {
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.0.extend_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+fn random_token() -> Result<String, String> {
+    let mut buf = [0; RANDOM_TOKEN_BYTES];
+    rand_bytes(&mut buf).map_err(|err| format!("{}", err))?;
+    Ok(base64::encode_config(&buf, base64::URL_SAFE_NO_PAD))
+}
+
+fn decode_base64url(s: &str) -> Result<Vec<u8>, String> {
+    base64::decode_config(s, base64::URL_SAFE_NO_PAD).map_err(|err| format!("invalid base64url: {}", err))
+}
+
+fn decode_header(id_token: &str) -> Result<JoseHeader, String> {
+    let header = id_token.split('.').next().unwrap_or("");
+    serde_json::from_slice(&decode_base64url(header)?).map_err(|err| format!("invalid header of the ID token: {}", err))
+}
+
+/// Returns the RSA signing key whose ID is `kid`, or the only one if the
+/// ID token does not specify its key.
+fn find_key(jwks: &Jwks, kid: Option<&str>) -> Option<Jwk> {
+    let mut keys = jwks.keys.iter()
+        .filter(|key| key.kty == "RSA" && key.use_.as_ref().map_or(true, |use_| use_ == "sig"));
+
+    match kid {
+        Some(kid) => keys.find(|key| key.kid.as_ref().map(String::as_str) == Some(kid)).cloned(),
+        None => {
+            let keys: Vec<&Jwk> = keys.collect();
+            if keys.len() == 1 { Some(keys[0].clone()) } else { None }
+        },
+    }
+}
+
+/// Verifies the signature of the ID token with `key` and returns its claims.
+fn verify_id_token(id_token: &str, header: &JoseHeader, key: &Jwk) -> Result<Value, String> {
+    let parts: Vec<&str> = id_token.split('.').collect();
+    if parts.len() != 3 {
+        return Err("the ID token is not a JWS in the compact serialization".to_string());
+    }
+
+    // Only asymmetric algorithms are accepted so that neither "none" nor
+    // the client secret as an HMAC key can be used to forge ID tokens.
+    let digest = match header.alg.as_str() {
+        "RS256" => MessageDigest::sha256(),
+        "RS384" => MessageDigest::sha384(),
+        "RS512" => MessageDigest::sha512(),
+        alg => return Err(format!("unsupported algorithm of the ID token: {}", alg)),
+    };
+
+    let (n, e) = match (key.n.as_ref(), key.e.as_ref()) {
+        (Some(n), Some(e)) => (n, e),
+        _ => return Err(format!("invalid RSA key: {:?}", key.kid)),
+    };
+
+    let verified = (|| {
+        let rsa = Rsa::from_public_components(BigNum::from_slice(&decode_base64url(n)?).map_err(|err| format!("{}", err))?,
+                                              BigNum::from_slice(&decode_base64url(e)?).map_err(|err| format!("{}", err))?)
+            .map_err(|err| format!("{}", err))?;
+        let pkey = PKey::from_rsa(rsa).map_err(|err| format!("{}", err))?;
+        let mut verifier = Verifier::new(digest, &pkey).map_err(|err| format!("{}", err))?;
+        verifier.update(format!("{}.{}", parts[0], parts[1]).as_bytes()).map_err(|err| format!("{}", err))?;
+        verifier.verify(&decode_base64url(parts[2])?).map_err(|err| format!("{}", err))
+    })()?;
+
+    if !verified {
+        return Err("invalid signature of the ID token".to_string());
+    }
+
+    serde_json::from_slice(&decode_base64url(parts[1])?).map_err(|err| format!("invalid claims of the ID token: {}", err))
+}
+
+/// Validates the issuer, the audience, the expiry and the nonce of the claims
+/// of an ID token at the Unix time `now`.
+fn validate_claims(claims: &Value, issuer: &str, client_id: &str, nonce: &str, now: i64) -> Result<(), String> {
+    if claims.get("iss").and_then(Value::as_str) != Some(issuer) {
+        return Err(format!("iss of the ID token is not {}", issuer));
+    }
+
+    let audiences: Vec<&str> = match claims.get("aud") {
+        Some(&Value::String(ref aud)) => vec![aud.as_str()],
+        Some(&Value::Array(ref aud)) => aud.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+
+    if !audiences.contains(&client_id) {
+        return Err(format!("aud of the ID token does not contain {}", client_id));
+    }
+
+    if audiences.len() > 1 && claims.get("azp").and_then(Value::as_str) != Some(client_id) {
+        return Err(format!("azp of the ID token is not {}", client_id));
+    }
+
+    match claims.get("exp").and_then(Value::as_i64) {
+        Some(exp) if now < exp + CLOCK_SKEW_SECS => (),
+        Some(_) => return Err("the ID token has expired".to_string()),
+        None => return Err("no exp claim in the ID token".to_string()),
+    }
+
+    if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+        return Err("nonce of the ID token does not match".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::sign::Signer;
+
+    #[test]
+    fn test_pending_login() {
+        let pending = PendingLogin::new().unwrap();
+
+        assert_eq!(Some(pending.clone()), PendingLogin::decode(&pending.encode()));
+        assert_eq!(None, PendingLogin::decode("a.b"));
+        assert_eq!(None, PendingLogin::decode("a..c"));
+
+        // RFC 7636 Appendix B
+        let pending = PendingLogin {
+            state: String::new(),
+            nonce: String::new(),
+            code_verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+        };
+        assert_eq!("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM", pending.code_challenge());
+    }
+
+    #[test]
+    fn test_verify_id_token() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = Jwk {
+            kty: "RSA".to_string(),
+            kid: Some("key1".to_string()),
+            use_: Some("sig".to_string()),
+            n: Some(base64::encode_config(&rsa.n().unwrap().to_vec(), base64::URL_SAFE_NO_PAD)),
+            e: Some(base64::encode_config(&rsa.e().unwrap().to_vec(), base64::URL_SAFE_NO_PAD)),
+        };
+        let pkey = PKey::from_rsa(rsa).unwrap();
+
+        let sign = |header: &str, claims: &str| {
+            let input = format!("{}.{}",
+                                base64::encode_config(header, base64::URL_SAFE_NO_PAD),
+                                base64::encode_config(claims, base64::URL_SAFE_NO_PAD));
+            let mut signer = Signer::new(MessageDigest::sha256(), &pkey).unwrap();
+            signer.update(input.as_bytes()).unwrap();
+            format!("{}.{}", input, base64::encode_config(&signer.sign_to_vec().unwrap(), base64::URL_SAFE_NO_PAD))
+        };
+
+        let id_token = sign(r#"{"alg":"RS256","kid":"key1"}"#, r#"{"sub":"alice"}"#);
+        let header = decode_header(&id_token).unwrap();
+        assert!(find_key(&Jwks { keys: vec![key.clone()] }, Some("key1")).is_some());
+        assert!(find_key(&Jwks { keys: vec![key.clone()] }, Some("key2")).is_none());
+        assert_eq!("alice", verify_id_token(&id_token, &header, &key).unwrap()["sub"]);
+
+        let tampered = id_token.replace(&base64::encode_config(r#"{"sub":"alice"}"#, base64::URL_SAFE_NO_PAD),
+                                        &base64::encode_config(r#"{"sub":"admin"}"#, base64::URL_SAFE_NO_PAD));
+        assert!(verify_id_token(&tampered, &header, &key).is_err());
+
+        let none = JoseHeader { alg: "none".to_string(), kid: None };
+        assert!(verify_id_token(&id_token, &none, &key).is_err());
+    }
+
+    #[test]
+    fn test_validate_claims() {
+        let claims: Value = serde_json::from_str(r#"{
+            "iss": "https://idp.example.com",
+            "aud": ["livy-manager", "other"],
+            "azp": "livy-manager",
+            "exp": 1000,
+            "nonce": "n"
+        }"#).unwrap();
+
+        assert!(validate_claims(&claims, "https://idp.example.com", "livy-manager", "n", 900).is_ok());
+        assert!(validate_claims(&claims, "https://idp.example.com", "livy-manager", "n", 1000 + CLOCK_SKEW_SECS).is_err());
+        assert!(validate_claims(&claims, "https://evil.example.com", "livy-manager", "n", 900).is_err());
+        assert!(validate_claims(&claims, "https://idp.example.com", "other-client", "n", 900).is_err());
+        assert!(validate_claims(&claims, "https://idp.example.com", "livy-manager", "m", 900).is_err());
+    }
+}
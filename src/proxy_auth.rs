@@ -0,0 +1,237 @@
+use authz;
+use config::{self, Config, ProxyAuth, ProxyUserAlias};
+use hyper::header::Headers;
+use openssl::rand::rand_bytes;
+use openssl::sha::Sha256;
+use server::UserSession;
+use std::net::IpAddr;
+use std::str;
+
+/// Range of IP addresses in the CIDR notation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    /// Parses a range such as `10.0.0.0/8`, or a single IP address.
+    pub fn parse(s: &str) -> Result<Cidr, String> {
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = parts.next().unwrap_or("").parse()
+            .map_err(|err| format!("{}: {}", s, err))?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match parts.next() {
+            Some(prefix_len) => match prefix_len.parse() {
+                Ok(prefix_len) if prefix_len <= max_len => prefix_len,
+                _ => return Err(format!("{}: invalid prefix length", s)),
+            },
+            None => max_len,
+        };
+
+        Ok(Cidr { addr: canonical(addr), prefix_len })
+    }
+
+    /// Returns whether `addr` is in the range.
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let (network, addr) = match (self.addr, canonical(*addr)) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => (network.octets().to_vec(), addr.octets().to_vec()),
+            (IpAddr::V6(network), IpAddr::V6(addr)) => (network.octets().to_vec(), addr.octets().to_vec()),
+            _ => return false,
+        };
+
+        let mut bits = self.prefix_len;
+        for (n, a) in network.iter().zip(addr.iter()) {
+            if bits == 0 {
+                break;
+            }
+            let mask = if bits >= 8 { 0xff } else { !(0xffu8 >> bits) };
+            if n & mask != a & mask {
+                return false;
+            }
+            bits = bits.saturating_sub(8);
+        }
+
+        true
+    }
+}
+
+/// Converts an IPv4-mapped IPv6 address such as `::ffff:10.0.0.1`, with which
+/// a dual-stack listener sees IPv4 clients, to the IPv4 address.
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, _, _] => IpAddr::V4(v6.to_ipv4().unwrap()),
+            _ => addr,
+        },
+        _ => addr,
+    }
+}
+
+/// Authenticator of users by the headers which a trusted reverse proxy sets
+pub struct Authenticator {
+    conf: ProxyAuth,
+    trusted_proxies: Vec<Cidr>,
+    teams: Vec<config::Team>,
+    proxy_user_aliases: Vec<ProxyUserAlias>,
+    /// Secret from which the CSRF tokens of users are derived, because there
+    /// are no login sessions to keep them in
+    csrf_secret: Vec<u8>,
+}
+
+impl Authenticator {
+    /// Creates a new `Authenticator` if `proxy_auth` is configured.
+    pub fn new(conf: &Config) -> Option<Authenticator> {
+        conf.proxy_auth.as_ref().map(|proxy_auth| {
+            let mut csrf_secret = vec![0; 32];
+            rand_bytes(&mut csrf_secret).unwrap();
+
+            Authenticator {
+                conf: proxy_auth.clone(),
+                // The CIDRs have been validated with the configuration.
+                trusted_proxies: proxy_auth.trusted_proxies.iter().filter_map(|proxy| Cidr::parse(proxy).ok()).collect(),
+                teams: conf.teams(),
+                proxy_user_aliases: conf.proxy_user_aliases(),
+                csrf_secret,
+            }
+        })
+    }
+
+    /// Returns the session of the user in the headers of a request from
+    /// `remote_addr`, or `None` if the request is not from a trusted proxy
+    /// or has no user header.
+    ///
+    /// A user header of a trusted proxy which does not have exactly one
+    /// value is an error rather than picking one of the values.
+    pub fn authenticate(&self, remote_addr: &IpAddr, headers: &Headers) -> Result<Option<UserSession>, String> {
+        let user_header = self.conf.user_header();
+
+        if headers.get_raw(&user_header).is_none() {
+            return Ok(None);
+        }
+
+        if !self.trusted_proxies.iter().any(|cidr| cidr.contains(remote_addr)) {
+            eprintln!("ignored {} from the untrusted address {}", user_header, remote_addr);
+            return Ok(None);
+        }
+
+        let uid = match header_value(headers, &user_header)? {
+            Some(uid) => uid,
+            None => return Ok(None),
+        };
+
+        let groups = header_values(headers, &self.conf.groups_header());
+
+        Ok(Some(UserSession {
+            roles: self.conf.roles(&groups),
+            proxy_users: authz::proxy_users(&self.proxy_user_aliases, &uid),
+            teams: authz::static_teams(&self.teams, &self.proxy_user_aliases, &uid),
+            uid,
+        }))
+    }
+
+    /// Returns the CSRF token of the user `uid`.
+    ///
+    /// The tokens change when Livy Manager is restarted, after which the
+    /// web UI has to be reloaded.
+    pub fn csrf_token(&self, uid: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.csrf_secret);
+        hasher.update(uid.as_bytes());
+        hasher.finish().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Returns the URL to which users are redirected on logout.
+    pub fn logout_url(&self) -> Option<&str> {
+        self.conf.logout_url.as_ref().map(String::as_str)
+    }
+}
+
+/// Returns the single value of the header `name`, or `None` if it is
+/// missing or empty. Several header lines or comma-separated values are
+/// rejected.
+fn header_value(headers: &Headers, name: &str) -> Result<Option<String>, String> {
+    let value = match headers.get_raw(name) {
+        Some(values) if values.len() == 1 => &values[0],
+        Some(_) => return Err(format!("{} must not be sent more than once", name)),
+        None => return Ok(None),
+    };
+
+    let value = str::from_utf8(value).map_err(|_| format!("{} is not valid UTF-8", name))?.trim();
+
+    if value.contains(',') {
+        return Err(format!("{} must have a single value", name));
+    }
+
+    Ok(if value.is_empty() { None } else { Some(value.to_string()) })
+}
+
+/// Returns the non-empty comma-separated values of the header `name`.
+fn header_values(headers: &Headers, name: &str) -> Vec<String> {
+    headers.get_raw(name).into_iter()
+        .flat_map(|values| values.iter())
+        .filter_map(|value| str::from_utf8(value).ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr() {
+        let cidr = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(cidr.contains(&"10.1.2.3".parse().unwrap()));
+        assert!(cidr.contains(&"::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains(&"10.2.0.1".parse().unwrap()));
+        assert!(!cidr.contains(&"::1".parse().unwrap()));
+
+        let cidr = Cidr::parse("10.0.0.128/25").unwrap();
+        assert!(cidr.contains(&"10.0.0.200".parse().unwrap()));
+        assert!(!cidr.contains(&"10.0.0.127".parse().unwrap()));
+
+        let cidr = Cidr::parse("127.0.0.1").unwrap();
+        assert!(cidr.contains(&"127.0.0.1".parse().unwrap()));
+        assert!(!cidr.contains(&"127.0.0.2".parse().unwrap()));
+
+        let cidr = Cidr::parse("fd00::/8").unwrap();
+        assert!(cidr.contains(&"fd12::1".parse().unwrap()));
+        assert!(!cidr.contains(&"fe80::1".parse().unwrap()));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(&"192.168.0.1".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("example.com").is_err());
+    }
+
+    #[test]
+    fn test_header_values() {
+        let mut headers = Headers::new();
+        headers.set_raw("X-Forwarded-Groups", vec![b"a, b,".to_vec(), b"c".to_vec()]);
+
+        assert_eq!(vec!["a", "b", "c"], header_values(&headers, "X-Forwarded-Groups"));
+        assert!(header_values(&headers, "X-Forwarded-User").is_empty());
+    }
+
+    #[test]
+    fn test_header_value() {
+        let mut headers = Headers::new();
+        assert_eq!(Ok(None), header_value(&headers, "X-Forwarded-User"));
+
+        headers.set_raw("X-Forwarded-User", vec![b" alice ".to_vec()]);
+        assert_eq!(Ok(Some("alice".to_string())), header_value(&headers, "X-Forwarded-User"));
+
+        headers.set_raw("X-Forwarded-User", vec![b"".to_vec()]);
+        assert_eq!(Ok(None), header_value(&headers, "X-Forwarded-User"));
+
+        headers.set_raw("X-Forwarded-User", vec![b"alice,admin".to_vec()]);
+        assert!(header_value(&headers, "X-Forwarded-User").is_err());
+
+        headers.set_raw("X-Forwarded-User", vec![b"alice".to_vec(), b"admin".to_vec()]);
+        assert!(header_value(&headers, "X-Forwarded-User").is_err());
+    }
+}
@@ -1,9 +1,19 @@
+use api_token::{self, Scope};
+use audit;
+use authn::{self, AuthError, Authenticator};
+use authz::{self, Action, Role, Team};
+use base64;
 use cmd_args::CmdArgs;
-use config::Config;
+use config::{self, Config};
 use frontend::html::index::INDEX;
 use frontend::html::login::LOGIN;
-use iron::BeforeMiddleware;
+use frontend::html::new_batch::NEW_BATCH;
+use frontend::html::new_session::NEW_SESSION;
+use frontend::html::session::SESSION;
+use frontend::html::tokens::TOKENS;
+use iron::{AfterMiddleware, BeforeMiddleware};
 use iron::headers::{CacheControl, CacheDirective, Connection, ContentType, Headers, Location, SetCookie};
+use iron::method::Method;
 use iron::mime;
 use iron::mime::{Attr, Mime, TopLevel, SubLevel};
 use iron::modifiers::Header;
@@ -12,21 +22,55 @@ use iron::status;
 use iron::status::Status;
 use iron::Timeouts;
 use iron::typemap::Key;
-use ldap;
+use kill_notification;
 use livy::client::Client;
+use livy_ext;
+use login_throttle;
+use new_batch::{self, NewBatchForm};
+use new_session::{self, NewSessionForm};
+use oidc;
 use params;
 use params::Params;
-use persistent::{Read, State};
+use persistent::Read;
+use proxy_auth;
 use router::Router;
+use serde::de::DeserializeOwned;
 use serde_json;
-use std::collections::HashMap;
+use session_store;
+use spnego;
 use std::error::Error;
 use std::fmt::{self, Debug};
+use std::io;
+use std::net::IpAddr;
+use std::str;
+use std::sync::Arc;
+use std::thread;
 use time;
 use time::Duration;
+use tls::{self, TlsServer};
 use uuid::Uuid;
 
 const COOKIE_NAME: &'static str = "_lmsid";
+/// Cookie which holds the CSRF token of the login form
+const LOGIN_CSRF_COOKIE_NAME: &'static str = "_lmcsrf";
+/// Cookie which holds the state, the nonce and the PKCE code verifier of
+/// an OpenID Connect login in progress
+const OIDC_COOKIE_NAME: &'static str = "_lmoidc";
+/// Scheme of the `Authorization` header which holds an API token
+const BEARER: &'static str = "Bearer ";
+/// Scheme of the `Authorization` header which holds a SPNEGO token
+const NEGOTIATE: &'static str = "Negotiate ";
+/// Seconds in which an OpenID Connect login has to be completed
+const OIDC_LOGIN_TIMEOUT_SECS: i64 = 600;
+/// Header which holds the CSRF token of the login session
+const CSRF_HEADER_NAME: &'static str = "X-CSRF-Token";
+const STATEMENT_OUTPUT_SUMMARY_MAX_CHARS: usize = 200;
+/// Maximum length of the reason of a kill
+const MAX_KILL_REASON_CHARS: usize = 1000;
+/// Maximum number of the events returned by `/api/audit`
+const MAX_AUDIT_EVENTS: i64 = 1000;
+/// Maximum size of a JSON request body in bytes
+const MAX_JSON_BODY_BYTES: u64 = 1024 * 1024;
 
 pub fn run() {
     let args = CmdArgs::new();
@@ -37,31 +81,92 @@ pub fn run() {
     }
 
     let conf = Config::from(&args.conf_path);
-    let user_sessions = UserSessions::new();
+    if let Some(ref spnego) = conf.spnego {
+        if let Err(err) = spnego::register_keytab(&spnego.keytab) {
+            panic!("invalid spnego configuration: {}", err);
+        }
+    }
+
+    let authenticator = Arc::new(authn::new(&conf));
+    let oidc_client = Arc::new(oidc::Client::new(&conf));
+    let proxy_authenticator = Arc::new(proxy_auth::Authenticator::new(&conf));
+    let api_tokens = Arc::new(conf.api_tokens.as_ref().map(|api_tokens| {
+        api_token::Store::new(&api_tokens.path, api_tokens.max_lifetime_days() * 24 * 60 * 60)
+    }));
+    let user_sessions = Arc::new(session_store::new(conf.session_store.as_ref()));
+    let login_throttle = Arc::new(login_throttle::Throttle::new(conf.login_throttle()));
+    let audit_log = Arc::new(audit::Log::new(conf.audit.as_ref()));
+    sweep_user_sessions(user_sessions.clone(), conf.session_timeout().sweep_interval_secs());
 
     let mut router = Router::new();
     router.get("/", index, "index");
     router.get("/login", login, "login");
     router.post("/login", auth, "auth");
+    router.get("/login/oidc", oidc_login, "oidc_login");
+    router.get(config::OIDC_CALLBACK_PATH, oidc_callback, "oidc_callback");
     router.get("/logout", logout, "logout");
+    router.get("/clusters/:cluster/sessions/:id", session, "session");
+    router.get("/new_session", new_session, "new_session");
+    router.get("/new_batch", new_batch, "new_batch");
+    router.get("/tokens", tokens, "tokens");
     router.get("/api/user_session", get_user_session, "get_user_session");
-    router.get("/api/sessions", get_sessions, "get_sessions");
-    router.delete("/api/sessions/:id", kill_session, "kill_session");
+    router.get("/api/clusters", get_clusters, "get_clusters");
+    router.get("/api/new_session", get_new_session_conf, "get_new_session_conf");
+    router.get("/api/batch_templates", get_batch_templates, "get_batch_templates");
+    router.get("/api/audit", get_audit_events, "get_audit_events");
+    router.get("/api/tokens", get_api_tokens, "get_api_tokens");
+    router.post("/api/tokens", create_api_token, "create_api_token");
+    router.delete("/api/tokens/:id", revoke_api_token, "revoke_api_token");
+    router.delete("/api/users/:uid/tokens", revoke_user_api_tokens, "revoke_user_api_tokens");
+    router.get("/api/sessions", get_all_sessions, "get_all_sessions");
+    router.get("/api/batches", get_all_batches, "get_all_batches");
+    router.get("/api/clusters/:cluster/sessions", get_sessions, "get_sessions");
+    router.post("/api/clusters/:cluster/sessions", create_session, "create_session");
+    router.get("/api/clusters/:cluster/sessions/:id", get_session, "get_session");
+    router.delete("/api/clusters/:cluster/sessions/:id", kill_session, "kill_session");
+    router.get("/api/clusters/:cluster/sessions/:id/log", get_session_log, "get_session_log");
+    router.get("/api/clusters/:cluster/sessions/:id/statements", get_statements, "get_statements");
+    router.post("/api/clusters/:cluster/sessions/:id/statements/:statement_id/cancel", cancel_statement, "cancel_statement");
+    router.get("/api/clusters/:cluster/batches", get_batches, "get_batches");
+    router.post("/api/clusters/:cluster/batches", create_batch, "create_batch");
+    router.get("/api/clusters/:cluster/batches/:id", get_batch, "get_batch");
+    router.delete("/api/clusters/:cluster/batches/:id", kill_batch, "kill_batch");
 
     eprintln!("Livy Manager {}", env!("CARGO_PKG_VERSION"));
-    eprintln!("Listening on {}.", conf.http.addr);
+    eprintln!("Listening on {}://{}.", if conf.http.tls.is_some() { "https" } else { "http" }, conf.http.addr);
 
     let mut chain = Chain::new(router);
     chain.link(Read::<Config>::both(conf.clone()));
-    chain.link(State::<UserSessions>::both(user_sessions));
+    chain.link(Read::<Authenticators>::both(authenticator));
+    chain.link(Read::<OidcClient>::both(oidc_client));
+    chain.link(Read::<ProxyAuthenticator>::both(proxy_authenticator));
+    chain.link(Read::<ApiTokens>::both(api_tokens));
+    chain.link(Read::<UserSessions>::both(user_sessions));
+    chain.link(Read::<LoginThrottle>::both(login_throttle));
+    chain.link(Read::<AuditLog>::both(audit_log));
     chain.link_before(UserSessionBeforeMiddleware);
+    chain.link_before(CsrfBeforeMiddleware);
+    chain.link_after(UserSessionAfterMiddleware);
 
     let iron = Iron {
         handler: chain,
         timeouts: Timeouts::default(),
         threads: conf.http.num_threads,
     };
-    iron.http(conf.http.addr).unwrap();
+
+    match conf.http.tls {
+        Some(ref tls) => {
+            let server = match TlsServer::new(tls) {
+                Ok(server) => server,
+                Err(err) => panic!("invalid TLS configuration: {}", err),
+            };
+            tls::reload_on_sighup(server.clone());
+            iron.https(conf.http.addr.as_str(), server).unwrap();
+        },
+        None => {
+            iron.http(conf.http.addr.as_str()).unwrap();
+        },
+    }
 }
 
 fn index(req: &mut Request) -> IronResult<Response> {
@@ -72,16 +177,120 @@ fn index(req: &mut Request) -> IronResult<Response> {
     Ok(response(status::Ok, INDEX, text_html()))
 }
 
+fn session(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Ok(redirect(status::TemporaryRedirect, "/login", None));
+    }
+
+    Ok(response(status::Ok, SESSION, text_html()))
+}
+
+fn new_session(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Ok(redirect(status::TemporaryRedirect, "/login", None));
+    }
+
+    Ok(response(status::Ok, NEW_SESSION, text_html()))
+}
+
+fn new_batch(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Ok(redirect(status::TemporaryRedirect, "/login", None));
+    }
+
+    Ok(response(status::Ok, NEW_BATCH, text_html()))
+}
+
+fn tokens(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Ok(redirect(status::TemporaryRedirect, "/login", None));
+    }
+
+    Ok(response(status::Ok, TOKENS, text_html()))
+}
+
 fn login(req: &mut Request) -> IronResult<Response> {
     if !auth_required(req) {
         return Ok(redirect(status::TemporaryRedirect, "/", None));
     }
 
     if req.extensions.get::<UserSessionBeforeMiddleware>().is_some() {
-        return Ok(redirect(status::TemporaryRedirect, "/", get_uuid(&req.headers).as_ref().map(String::as_str)));
+        return Ok(redirect(status::TemporaryRedirect, "/", req.extensions.get::<SessionCookie>()));
     }
 
-    Ok(response(status::Ok, LOGIN, text_html()))
+    // The token is sent back both as a cookie and as a form field, which
+    // a cross-site form cannot do.
+    let csrf_token = Uuid::new_v4().simple().to_string();
+
+    let arc = req.get::<Read<Config>>().unwrap();
+
+    // Users are authenticated only by the reverse proxy.
+    if !arc.as_ref().login_enabled() {
+        return Ok(response(status::Unauthorized, "Please access Livy Manager through the authenticating proxy.", text_html()));
+    }
+
+    // Browsers which support SPNEGO send the Kerberos tickets of their users
+    // after a 401 response with `WWW-Authenticate: Negotiate`, and the others
+    // show its body, which is the login form.
+    let mut negotiate = arc.as_ref().spnego.is_some() && !has_query(req, "sso=false");
+
+    if let (true, Some(token)) = (negotiate, negotiate_token(&req.headers)) {
+        match spnego::authenticate(arc.as_ref(), &token) {
+            Ok((user_session, output)) => {
+                let mut res = log_in(req, user_session, "spnego")?;
+                if !output.is_empty() {
+                    res.headers.set_raw("WWW-Authenticate", vec![format!("Negotiate {}", base64::encode(&output)).into_bytes()]);
+                }
+                return Ok(res);
+            },
+            Err(err) => {
+                // The login form is shown instead of asking for another token.
+                eprintln!("SPNEGO authentication failed: {}", err);
+                let mut event = audit_event(req, None, audit::Action::Login, audit::Outcome::Failure);
+                event.detail = Some(format!("spnego: {}", err));
+                audit(req, &event);
+                negotiate = false;
+            },
+        }
+    }
+
+    let hidden = |hidden: bool| if hidden { "hidden" } else { "" };
+    let page = LOGIN.replace("{{csrf_token}}", &csrf_token)
+        .replace("{{password_login_hidden}}", hidden(arc.as_ref().authenticators().is_empty()))
+        .replace("{{oidc_login_hidden}}", hidden(arc.as_ref().oidc.is_none()));
+
+    let mut res = Response::with((
+        if negotiate { status::Unauthorized } else { status::Ok },
+        page,
+        cache_control(),
+        connection(),
+        text_html(),
+        Header(SetCookie(vec![format!("{}={}; path=/login", LOGIN_CSRF_COOKIE_NAME, csrf_token)])),
+    ));
+
+    if negotiate {
+        res.headers.set_raw("WWW-Authenticate", vec![b"Negotiate".to_vec()]);
+    }
+
+    Ok(res)
+}
+
+/// Returns the SPNEGO token in the `Authorization: Negotiate` header.
+fn negotiate_token(headers: &Headers) -> Option<Vec<u8>> {
+    let value = headers.get_raw("Authorization")
+        .and_then(|values| values.first())
+        .and_then(|value| str::from_utf8(value).ok())?;
+
+    if value.len() <= NEGOTIATE.len() || !value[..NEGOTIATE.len()].eq_ignore_ascii_case(NEGOTIATE) {
+        return None;
+    }
+
+    base64::decode(value[NEGOTIATE.len()..].trim()).ok()
+}
+
+/// Returns whether the query string of the request contains `param`.
+fn has_query(req: &Request, param: &str) -> bool {
+    req.url.query().map_or(false, |query| query.split('&').any(|p| p == param))
 }
 
 fn auth(req: &mut Request) -> IronResult<Response> {
@@ -90,7 +299,7 @@ fn auth(req: &mut Request) -> IronResult<Response> {
     }
 
     if req.extensions.get::<UserSessionBeforeMiddleware>().is_some() {
-        return Ok(redirect(status::SeeOther, "/", get_uuid(&req.headers).as_ref().map(String::as_str)));
+        return Ok(redirect(status::SeeOther, "/", req.extensions.get::<SessionCookie>()));
     }
 
     let params = match req.get_ref::<Params>() {
@@ -98,58 +307,919 @@ fn auth(req: &mut Request) -> IronResult<Response> {
         Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
     };
 
+    match (params.find(&["csrf_token"]), get_cookie(&req.headers, LOGIN_CSRF_COOKIE_NAME)) {
+        (Some(&params::Value::String(ref token)), Some(ref expected)) if secure_eq(token, expected) => (),
+        _ => return Err(json_error(status::Forbidden, "invalid CSRF token".to_string())),
+    }
+
     match (params.find(&["uid"]), params.find(&["password"])) {
         (Some(&params::Value::String(ref uid)), Some(&params::Value::String(ref password))) => {
-            let arc = req.get::<Read<Config>>().unwrap();
-            let conf = match arc.as_ref().ldap.clone() {
-                Some(conf) => conf,
-                None => return Err(IronError::new(StringError("invalid request".to_string()), status::BadRequest))
+            let throttle = req.get::<Read<LoginThrottle>>().unwrap();
+            let ip = client_ip(req);
+            let now = time::get_time().sec;
+
+            // Throttled logins get the same response as failed ones, and are
+            // not sent to the authenticators.
+            let attempt = match throttle.attempt(uid, &ip, now) {
+                Ok(attempt) => attempt,
+                Err(retry_after) => {
+                    eprintln!("login of {} from {} was throttled for {} seconds", uid, ip, retry_after);
+                    let mut event = audit_event(req, Some(uid.to_string()), audit::Action::Login, audit::Outcome::Denied);
+                    event.detail = Some(format!("password: throttled for {} seconds", retry_after));
+                    audit(req, &event);
+                    return Ok(redirect(status::SeeOther, "/login?result=failed", None));
+                },
             };
 
-            match ldap::auth(&conf, uid.as_str(), password.as_str()) {
+            let authenticator = req.get::<Read<Authenticators>>().unwrap();
+
+            match authenticator.authenticate(uid.as_str(), password.as_str()) {
                 Ok(user_session) => {
-                    let uuid = Uuid::new_v4().to_string();
-                    let arc = req.get::<State<UserSessions>>().unwrap();
-                    let lock = arc.as_ref();
-                    let mut user_sessions = lock.write().unwrap();
-                    if user_sessions.map.contains_key(&uuid) {
-                        return Ok(redirect(status::SeeOther, "/login?result=failed", None));
+                    throttle.succeed(attempt);
+                    log_in(req, user_session, "password")
+                },
+                Err(err) => {
+                    // Only wrong credentials are counted as failures, not
+                    // errors such as an outage of the LDAP server.
+                    if let AuthError::Unavailable(_) = err {
+                        throttle.release(attempt);
                     }
-                    user_sessions.map.insert(uuid.clone(), user_session);
-                    return Ok(redirect(status::SeeOther, "/", Some(&uuid)));
+                    eprintln!("authentication of {} from {} failed: {}", uid, ip, err);
+                    let mut event = audit_event(req, Some(uid.to_string()), audit::Action::Login, audit::Outcome::Failure);
+                    event.detail = Some(format!("password: {}", err));
+                    audit(req, &event);
+                    Ok(redirect(status::SeeOther, "/login?result=failed", None))
                 },
-                Err(_) => Ok(redirect(status::SeeOther, "/login?result=failed", None))
             }
         },
-        _ => Err(IronError::new(StringError("invalid parameters".to_string()), status::BadRequest)),
-    }
+        _ => Err(IronError::new(StringError("invalid parameters".to_string()), status::BadRequest)),
+    }
+}
+
+/// Redirects the user to the OpenID provider to log in.
+fn oidc_login(req: &mut Request) -> IronResult<Response> {
+    if req.extensions.get::<UserSessionBeforeMiddleware>().is_some() {
+        return Ok(redirect(status::TemporaryRedirect, "/", req.extensions.get::<SessionCookie>()));
+    }
+
+    let client = req.get::<Read<OidcClient>>().unwrap();
+    let client = match client.as_ref() {
+        Some(client) => client,
+        None => return Err(IronError::new(StringError("oidc is not configured".to_string()), status::NotFound)),
+    };
+
+    let pending = oidc::PendingLogin::new()
+        .map_err(|err| IronError::new(StringError(err), status::InternalServerError))?;
+
+    match client.authorization_url(&pending) {
+        Ok(url) => Ok(Response::with((
+            status::TemporaryRedirect,
+            cache_control(),
+            connection(),
+            text_html(),
+            Header(Location(url)),
+            Header(SetCookie(vec![format!("{}={}; max-age={}; path={}",
+                                          OIDC_COOKIE_NAME, pending.encode(), OIDC_LOGIN_TIMEOUT_SECS, config::OIDC_CALLBACK_PATH)])),
+        ))),
+        Err(err) => {
+            eprintln!("OpenID Connect login failed: {}", err);
+            Ok(redirect(status::TemporaryRedirect, "/login?result=failed", None))
+        },
+    }
+}
+
+/// Logs the user in with the authorization code which the OpenID provider
+/// has redirected the user back with.
+fn oidc_callback(req: &mut Request) -> IronResult<Response> {
+    let client = req.get::<Read<OidcClient>>().unwrap();
+    let client = match client.as_ref() {
+        Some(client) => client,
+        None => return Err(IronError::new(StringError("oidc is not configured".to_string()), status::NotFound)),
+    };
+
+    let params = match req.get_ref::<Params>() {
+        Ok(params) => params.clone(),
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
+    };
+
+    let pending = get_cookie(&req.headers, OIDC_COOKIE_NAME).and_then(|cookie| oidc::PendingLogin::decode(&cookie));
+
+    let result = match (params.find(&["code"]), params.find(&["state"]), pending) {
+        (Some(&params::Value::String(ref code)), Some(&params::Value::String(ref state)), Some(ref pending))
+            if secure_eq(state, &pending.state) => client.authenticate(pending, code),
+        _ => match params.find(&["error"]) {
+            Some(&params::Value::String(ref error)) => Err(format!("error from the OpenID provider: {}", error)),
+            _ => Err("invalid state".to_string()),
+        },
+    };
+
+    let mut res = match result {
+        Ok(user_session) => log_in(req, user_session, "oidc")?,
+        Err(err) => {
+            eprintln!("OpenID Connect login failed: {}", err);
+            let mut event = audit_event(req, None, audit::Action::Login, audit::Outcome::Failure);
+            event.detail = Some(format!("oidc: {}", err));
+            audit(req, &event);
+            redirect(status::SeeOther, "/login?result=failed", None)
+        },
+    };
+
+    // The pending login is used only once.
+    if let Some(&mut SetCookie(ref mut cookies)) = res.headers.get_mut::<SetCookie>() {
+        cookies.push(format!("{}=; max-age=0; path={}", OIDC_COOKIE_NAME, config::OIDC_CALLBACK_PATH));
+    }
+
+    Ok(res)
+}
+
+/// Creates a login session of `user_session`, which has been authenticated
+/// by `method`, and redirects the user to the top page.
+fn log_in(req: &mut Request, user_session: UserSession, method: &str) -> IronResult<Response> {
+    let mut event = audit_event(req, Some(user_session.uid.clone()), audit::Action::Login, audit::Outcome::Success);
+    event.detail = Some(method.to_string());
+    let timeout = req.get::<Read<Config>>().unwrap().as_ref().session_timeout();
+    let now = time::get_time().sec;
+    let expires_at = now + timeout.absolute_secs();
+    let entry = session_store::Entry {
+        user_session,
+        csrf_token: Uuid::new_v4().simple().to_string(),
+        expires_at,
+        idle_expires_at: timeout.idle_secs.map(|idle_secs| now + idle_secs).unwrap_or(expires_at),
+    };
+    let cookie = SessionCookie {
+        id: Uuid::new_v4().to_string(),
+        expires_at: entry.valid_until(),
+        renewed: false,
+    };
+    // The API tokens of the user get the roles, proxy users and teams of
+    // the login, so that they lose the rights which the user has lost.
+    if let Some(ref store) = *req.get::<Read<ApiTokens>>().unwrap().as_ref() {
+        if let Err(err) = store.refresh(&entry.user_session) {
+            return Err(IronError::new(StringError(err), status::InternalServerError));
+        }
+    }
+
+    let arc = req.get::<Read<UserSessions>>().unwrap();
+    match arc.as_ref().insert(&cookie.id, entry) {
+        Ok(true) => {
+            audit(req, &event);
+            Ok(redirect(status::SeeOther, "/", Some(&cookie)))
+        },
+        Ok(false) => Ok(redirect(status::SeeOther, "/login?result=failed", None)),
+        Err(err) => Err(IronError::new(StringError(err), status::InternalServerError)),
+    }
+}
+
+fn logout(req: &mut Request) -> IronResult<Response> {
+    if !auth_required(req) {
+        return Ok(redirect(status::TemporaryRedirect, "/", None));
+    }
+
+    if let (Some(_), Some(uuid)) = (req.extensions.get::<UserSessionBeforeMiddleware>(), get_uuid(&req.headers)) {
+        let arc = req.get::<Read<UserSessions>>().unwrap();
+        if let Err(err) = arc.as_ref().remove(&uuid) {
+            return Err(IronError::new(StringError(err), status::InternalServerError));
+        }
+    }
+
+    let uid = req.extensions.get::<UserSessionBeforeMiddleware>().map(|user_session| user_session.uid.clone());
+    if uid.is_some() {
+        let event = audit_event(req, uid, audit::Action::Logout, audit::Outcome::Success);
+        audit(req, &event);
+    }
+
+    // Users authenticated by the reverse proxy have to log out of it.
+    if req.extensions.get::<UserSessionBeforeMiddleware>().is_some() && req.extensions.get::<SessionCookie>().is_none() {
+        let arc = req.get::<Read<ProxyAuthenticator>>().unwrap();
+        if let Some(url) = arc.as_ref().as_ref().and_then(|authenticator| authenticator.logout_url()) {
+            return Ok(redirect(status::TemporaryRedirect, url, None));
+        }
+    }
+
+    // The login form is shown without the SPNEGO single sign-on, which
+    // would log the user in again.
+    Ok(redirect(status::TemporaryRedirect, "/login?sso=false", None))
+}
+
+fn get_user_session(req: &mut Request) -> IronResult<Response> {
+    let api_tokens = req.get::<Read<ApiTokens>>().unwrap().is_some();
+    let user_session = match (req.extensions.get::<UserSessionBeforeMiddleware>(), req.extensions.get::<CsrfToken>()) {
+        (Some(user_session), Some(csrf_token)) => Some(UserSessionInfo {
+            uid: &user_session.uid,
+            is_admin: user_session.is_admin(),
+            roles: &user_session.roles,
+            teams: user_session.teams.iter().map(|team| team.name.as_str()).collect(),
+            api_tokens,
+            csrf_token,
+        }),
+        _ => None,
+    };
+
+    match serde_json::to_string(&user_session) {
+        Ok(user_session) => Ok(response(status::Ok, &user_session, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// API token returned by `/api/tokens`, without its hash
+#[derive(Serialize)]
+struct ApiTokenInfo<'a> {
+    /// Token sent as `Authorization: Bearer`, which is returned only when
+    /// the token is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<&'a str>,
+    id: &'a str,
+    name: &'a str,
+    scope: Scope,
+    created_at: i64,
+    expires_at: Option<i64>,
+}
+
+impl<'a> From<&'a api_token::Token> for ApiTokenInfo<'a> {
+    fn from(token: &'a api_token::Token) -> ApiTokenInfo<'a> {
+        ApiTokenInfo {
+            token: None,
+            id: &token.id,
+            name: &token.name,
+            scope: token.scope,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
+
+/// Request body of `POST /api/tokens`
+#[derive(Deserialize)]
+struct NewApiToken {
+    name: String,
+    scope: Scope,
+    /// Days after which the token expires, or `None` for the maximum lifetime
+    expires_in_days: Option<i64>,
+}
+
+/// Returns the session of the user logged in without an API token, because API
+/// tokens cannot be managed with API tokens.
+fn api_token_owner(req: &Request) -> IronResult<UserSession> {
+    if req.extensions.get::<ApiTokenScope>().is_some() {
+        return Err(json_error(status::Forbidden, "API tokens cannot be managed with API tokens".to_string()));
+    }
+
+    match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Ok(user_session.clone()),
+        None => Err(IronError::new(StringError(String::new()), status::Unauthorized)),
+    }
+}
+
+fn get_api_tokens(req: &mut Request) -> IronResult<Response> {
+    let user_session = api_token_owner(req)?;
+
+    let arc = req.get::<Read<ApiTokens>>().unwrap();
+    let store = match *arc.as_ref() {
+        Some(ref store) => store,
+        None => return Err(json_error(status::NotFound, "API tokens are not enabled".to_string())),
+    };
+
+    let tokens = match store.list(&user_session.uid) {
+        Ok(tokens) => tokens,
+        Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+    };
+    let tokens: Vec<ApiTokenInfo> = tokens.iter().map(ApiTokenInfo::from).collect();
+
+    match serde_json::to_string(&tokens) {
+        Ok(tokens) => Ok(response(status::Ok, &tokens, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+fn create_api_token(req: &mut Request) -> IronResult<Response> {
+    let user_session = api_token_owner(req)?;
+    let form = read_json::<NewApiToken>(req)?;
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(json_error(status::BadRequest, "name is required".to_string()));
+    }
+
+    let max_lifetime_days = req.get::<Read<Config>>().unwrap().as_ref().api_tokens.as_ref()
+        .map_or(0, |api_tokens| api_tokens.max_lifetime_days());
+
+    let expires_at = match form.expires_in_days {
+        Some(days) if days <= 0 => return Err(json_error(status::BadRequest, "expires_in_days must be positive".to_string())),
+        Some(days) if days > max_lifetime_days => {
+            return Err(json_error(status::BadRequest, format!("expires_in_days must be at most {}", max_lifetime_days)));
+        },
+        Some(days) => Some(time::get_time().sec + days * 24 * 60 * 60),
+        None => None,
+    };
+
+    let arc = req.get::<Read<ApiTokens>>().unwrap();
+    let store = match *arc.as_ref() {
+        Some(ref store) => store,
+        None => return Err(json_error(status::NotFound, "API tokens are not enabled".to_string())),
+    };
+
+    let (token, bearer) = match store.create(user_session, name, form.scope, expires_at) {
+        Ok(created) => created,
+        Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+    };
+
+    let created = ApiTokenInfo {
+        token: Some(&bearer),
+        ..ApiTokenInfo::from(&token)
+    };
+
+    match serde_json::to_string(&created) {
+        Ok(created) => Ok(response(status::Created, &created, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+fn revoke_api_token(req: &mut Request) -> IronResult<Response> {
+    let user_session = api_token_owner(req)?;
+    let id = req.extensions.get::<Router>().unwrap()
+        .find("id").unwrap_or("").to_string();
+
+    let arc = req.get::<Read<ApiTokens>>().unwrap();
+    let store = match *arc.as_ref() {
+        Some(ref store) => store,
+        None => return Err(json_error(status::NotFound, "API tokens are not enabled".to_string())),
+    };
+
+    match store.revoke(&user_session.uid, &id) {
+        Ok(true) => Ok(response(status::Ok, "{}", application_json())),
+        Ok(false) => Err(json_error(status::NotFound, format!("unknown API token: {}", id))),
+        Err(err) => Err(IronError::new(StringError(err), status::InternalServerError)),
+    }
+}
+
+/// Response of `DELETE /api/users/:uid/tokens`
+#[derive(Serialize)]
+struct RevokedApiTokens {
+    /// Number of the revoked tokens
+    revoked: usize,
+}
+
+/// Revokes all API tokens of another user, such as a user whose rights
+/// have been reduced. Only admin users can do it.
+fn revoke_user_api_tokens(req: &mut Request) -> IronResult<Response> {
+    let user_session = api_token_owner(req)?;
+    let uid = req.extensions.get::<Router>().unwrap()
+        .find("uid").unwrap_or("").to_string();
+
+    let mut event = audit_event(req, Some(user_session.uid.clone()), audit::Action::RevokeApiTokens, audit::Outcome::Success);
+    event.detail = Some(uid.clone());
+
+    if !authz::is_allowed(true, Some(&user_session), Action::Administer, || None) {
+        event.outcome = audit::Outcome::Denied;
+        audit(req, &event);
+        return Err(json_error(status::Forbidden, "only admin users can revoke the API tokens of other users".to_string()));
+    }
+
+    let revoked = {
+        let arc = req.get::<Read<ApiTokens>>().unwrap();
+        let store = match *arc.as_ref() {
+            Some(ref store) => store,
+            None => return Err(json_error(status::NotFound, "API tokens are not enabled".to_string())),
+        };
+
+        match store.revoke_all(&uid) {
+            Ok(revoked) => revoked,
+            Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+        }
+    };
+
+    audit(req, &event);
+
+    match serde_json::to_string(&RevokedApiTokens { revoked }) {
+        Ok(revoked) => Ok(response(status::Ok, &revoked, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+fn get_clusters(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let clusters = {
+        let arc = req.get::<Read<Config>>().unwrap();
+        arc.as_ref().clusters().iter().map(|cluster| cluster.name().to_string()).collect::<Vec<_>>()
+    };
+
+    match serde_json::to_string(&clusters) {
+        Ok(clusters) => Ok(response(status::Ok, &clusters, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+fn get_sessions(req: &mut Request) -> IronResult<Response> {
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if auth_required && user_session.is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let conf = route_cluster(req)?;
+
+    let sessions = match list_sessions(&conf, auth_required, user_session.as_ref()) {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError))
+        },
+    };
+
+    let sessions = match serde_json::to_string(&sessions) {
+        Ok(sessions) => sessions,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Ok, &sessions, application_json()))
+}
+
+fn get_all_sessions(req: &mut Request) -> IronResult<Response> {
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if auth_required && user_session.is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let clusters = query_clusters(req)?;
+
+    let sessions = fetch_from_clusters(clusters, move |conf| {
+        list_sessions(conf, auth_required, user_session.as_ref())
+    });
+
+    match serde_json::to_string(&sessions) {
+        Ok(sessions) => Ok(response(status::Ok, &sessions, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// Returns the sessions of the Livy cluster which can be seen by the user.
+fn list_sessions(conf: &config::LivyClient, auth_required: bool, user_session: Option<&UserSession>) -> Result<Vec<livy_ext::Session>, String> {
+    let sessions = livy_ext::get_sessions(conf, None, None)?;
+
+    Ok(sessions.sessions.unwrap_or_default().into_iter().filter(|session| {
+        authz::is_allowed(auth_required, user_session, Action::View, || session.proxy_user.clone())
+    }).map(|mut session| {
+        session.cluster = Some(conf.name().to_string());
+        session.log = None;
+        session
+    }).collect())
+}
+
+fn get_session(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    let conf = route_cluster(req)?;
+
+    let mut session = match livy_ext::get_session(&conf, id) {
+        Ok(session) => session,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    if !authz::is_allowed(auth_required, user_session.as_ref(), Action::View, || session.proxy_user.clone()) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    session.cluster = Some(conf.name().to_string());
+    session.log = None;
+
+    let session = match serde_json::to_string(&session) {
+        Ok(session) => session,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Ok, &session, application_json()))
+}
+
+fn get_session_log(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+    let from = query_i64(req, "from")?;
+    let size = query_i64(req, "size")?;
+
+    let conf = route_cluster(req)?;
+    let client = livy_client(&conf);
+
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if !has_session_authority(&conf, id, Action::View, auth_required(req), user_session.as_ref()) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let log = match client.get_session_log(id, from, size) {
+        Ok(log) => log,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    let log = SessionLog {
+        cluster: conf.name().to_string(),
+        id: log.id,
+        from: log.from,
+        total: log.total,
+        log: log.log,
+    };
+
+    let log = match serde_json::to_string(&log) {
+        Ok(log) => log,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Ok, &log, application_json()))
+}
+
+fn get_statements(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+
+    let conf = route_cluster(req)?;
+
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if !has_session_authority(&conf, id, Action::View, auth_required(req), user_session.as_ref()) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let statements = match livy_ext::get_statements(&conf, id) {
+        Ok(statements) => statements,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    let statements = statements.statements.unwrap_or_default().iter().map(|statement| {
+        StatementSummary {
+            cluster: conf.name().to_string(),
+            id: statement.id,
+            code: statement.code.clone(),
+            state: statement.state.clone(),
+            progress: statement.progress,
+            output: statement.output_summary(STATEMENT_OUTPUT_SUMMARY_MAX_CHARS),
+        }
+    }).collect::<Vec<_>>();
+
+    let statements = match serde_json::to_string(&statements) {
+        Ok(statements) => statements,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Ok, &statements, application_json()))
+}
+
+fn cancel_statement(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+    let statement_id = route_id(req, "statement_id")?;
+
+    let conf = route_cluster(req)?;
+    let client = livy_client(&conf);
+
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if !has_session_authority(&conf, id, Action::Operate, auth_required(req), user_session.as_ref()) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    match client.cancel_statement(id, statement_id) {
+        Ok(result) => operation_result(&conf, result.msg),
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// Session log with the name of the Livy cluster
+#[derive(Serialize)]
+struct SessionLog {
+    cluster: String,
+    id: Option<i64>,
+    from: Option<i64>,
+    total: Option<i64>,
+    log: Option<Vec<String>>,
+}
+
+/// Statement information shown on the session page
+#[derive(Serialize)]
+struct StatementSummary {
+    cluster: String,
+    id: Option<i64>,
+    code: Option<String>,
+    state: Option<String>,
+    progress: Option<f64>,
+    output: Option<String>,
+}
+
+fn create_session(req: &mut Request) -> IronResult<Response> {
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    if !authz::is_allowed(auth_required, user_session.as_ref(), Action::Create, || None) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let form = read_json::<NewSessionForm>(req)?;
+
+    let limits = {
+        let arc = req.get::<Read<Config>>().unwrap();
+        arc.as_ref().new_session.as_ref().and_then(|new_session| new_session.limits.clone())
+    };
+
+    let request = match new_session::build_request(form, user_session.as_ref(), limits.as_ref()) {
+        Ok(request) => request,
+        Err(err) => return Err(json_error(status::BadRequest, err)),
+    };
+
+    let conf = route_cluster(req)?;
+
+    let mut session = match livy_ext::create_session(&conf, &request) {
+        Ok(session) => session,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    session.cluster = Some(conf.name().to_string());
+
+    let session = match serde_json::to_string(&session) {
+        Ok(session) => session,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Created, &session, application_json()))
+}
+
+fn get_new_session_conf(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    let new_session = {
+        let arc = req.get::<Read<Config>>().unwrap();
+        arc.as_ref().new_session.clone()
+    };
+
+    let new_session_conf = NewSessionConf {
+        kinds: new_session::KINDS.to_vec(),
+        presets: new_session.as_ref().and_then(|new_session| new_session.presets.clone()).unwrap_or_default(),
+        limits: new_session.and_then(|new_session| new_session.limits),
+    };
+
+    match serde_json::to_string(&new_session_conf) {
+        Ok(new_session_conf) => Ok(response(status::Ok, &new_session_conf, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// Information used to populate the new session form
+#[derive(Serialize)]
+struct NewSessionConf {
+    kinds: Vec<&'static str>,
+    presets: Vec<config::SessionPreset>,
+    limits: Option<config::ResourceLimits>,
+}
+
+fn kill_session(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+
+    let conf = route_cluster(req)?;
+    let client = livy_client(&conf);
+
+    // The session is fetched once for the authorization, the audit log and the notification.
+    let session = livy_ext::get_session(&conf, id).ok();
+    let proxy_user = session.as_ref().and_then(|session| session.proxy_user.clone());
+    let app_id = session.and_then(|session| session.app_id);
+
+    kill(req, &conf, id, audit::Action::KillSession, proxy_user, app_id, || {
+        client.kill_session(id).map(|result| result.msg)
+    })
+}
+
+/// Kills the session or batch `id` of `conf` with `perform` if the user is allowed to,
+/// records it in the audit log, and notifies its proxy user if it is not the user.
+///
+/// The reason of the kill is given as `{"reason": "..."}` in the request body,
+/// which is required unless the session or batch is the user's own.
+fn kill<F>(req: &mut Request, conf: &config::LivyClient, id: i64, action: audit::Action,
+           proxy_user: Option<String>, app_id: Option<String>, perform: F) -> IronResult<Response>
+    where F: FnOnce() -> Result<Option<String>, String> {
+    let reason = kill_reason(req)?;
+    let auth_required = auth_required(req);
+
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    let mut event = audit_event(req, user_session.as_ref().map(|u| u.uid.clone()), action, audit::Outcome::Success);
+    event.cluster = Some(conf.name().to_string());
+    event.target_id = Some(id);
+    event.proxy_user = proxy_user.clone();
+    event.app_id = app_id;
+    event.reason = reason;
+
+    if !authz::is_allowed(auth_required, user_session.as_ref(), Action::Operate, || proxy_user.clone()) {
+        event.outcome = audit::Outcome::Denied;
+        audit(req, &event);
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
+
+    // Anonymous kills without the authentication are treated as the user's own.
+    let is_own = match (user_session.as_ref(), proxy_user.as_ref()) {
+        (Some(user_session), Some(proxy_user)) => authz::is_own(user_session, proxy_user),
+        _ => !auth_required,
+    };
+
+    if !is_own && event.reason.is_none() {
+        event.outcome = audit::Outcome::Denied;
+        event.detail = Some("no reason".to_string());
+        audit(req, &event);
+        return Err(json_error(status::BadRequest, "a reason is required to kill a session or batch of another user".to_string()));
+    }
+
+    match perform() {
+        Ok(msg) => {
+            audit(req, &event);
+            if !is_own {
+                // Without the audit log, the reason is kept only in the log of Livy Manager.
+                if req.get::<Read<Config>>().unwrap().as_ref().audit_path().is_none() {
+                    let kind = if action == audit::Action::KillBatch { "batch" } else { "session" };
+                    eprintln!("warning: {} {} on {} was killed by {} without audit.path: {}", kind, id, conf.name(),
+                              event.uid.as_ref().map_or("-", String::as_str), event.reason.as_ref().map_or("", String::as_str));
+                }
+                notify_kill(req, &event);
+            }
+            operation_result(conf, msg)
+        },
+        Err(err) => {
+            event.outcome = audit::Outcome::Failure;
+            event.detail = Some(err.clone());
+            audit(req, &event);
+            Err(IronError::new(StringError(err), status::InternalServerError))
+        },
+    }
+}
+
+/// Request body of a kill
+#[derive(Deserialize)]
+struct KillRequest {
+    reason: Option<String>,
+}
+
+/// Returns the non-empty reason in the optional request body of a kill.
+fn kill_reason(req: &mut Request) -> IronResult<Option<String>> {
+    let body = read_body(req)?;
+
+    if body.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let reason = match serde_json::from_str::<KillRequest>(&body) {
+        Ok(kill_request) => kill_request.reason.map(|reason| reason.trim().to_string()),
+        Err(err) => return Err(json_error(status::BadRequest, format!("invalid request body: {}", err))),
+    };
+
+    match reason {
+        Some(ref reason) if reason.chars().count() > MAX_KILL_REASON_CHARS => {
+            Err(json_error(status::BadRequest, format!("reason must be at most {} characters", MAX_KILL_REASON_CHARS)))
+        },
+        Some(ref reason) if reason.is_empty() => Ok(None),
+        reason => Ok(reason),
+    }
+}
+
+/// Notifies the proxy user of the killed session or batch of `event`
+/// in the background if `kill_notification` is configured.
+fn notify_kill(req: &mut Request, event: &audit::Event) {
+    let conf = match req.get::<Read<Config>>().unwrap().as_ref().kill_notification.clone() {
+        Some(conf) => conf,
+        None => return,
+    };
+
+    let kill = match (event.cluster.as_ref(), event.target_id, event.proxy_user.as_ref(), event.uid.as_ref(), event.reason.as_ref()) {
+        (Some(cluster), Some(id), Some(proxy_user), Some(uid), Some(reason)) => kill_notification::Kill {
+            kind: if event.action == audit::Action::KillBatch { "batch" } else { "session" },
+            cluster: cluster.clone(),
+            id,
+            proxy_user: proxy_user.clone(),
+            app_id: event.app_id.clone(),
+            killed_by: uid.clone(),
+            reason: reason.clone(),
+        },
+        _ => return,
+    };
+
+    thread::spawn(move || {
+        if let Err(err) = kill_notification::send(&conf, &kill) {
+            eprintln!("failed to notify {} of the kill of {} {} on {}: {}", kill.proxy_user, kill.kind, kill.id, kill.cluster, err);
+        }
+    });
 }
 
-fn logout(req: &mut Request) -> IronResult<Response> {
-    if !auth_required(req) {
-        return Ok(redirect(status::TemporaryRedirect, "/", None));
-    }
+/// Result of an operation on a Livy cluster
+#[derive(Serialize)]
+struct OperationResult {
+    cluster: String,
+    msg: Option<String>,
+}
 
-    if let (Some(_), Some(uuid)) = (req.extensions.get::<UserSessionBeforeMiddleware>(), get_uuid(&req.headers)) {
-        let arc = req.get::<State<UserSessions>>().unwrap();
-        let lock = arc.as_ref();
-        let mut user_sessions = lock.write().unwrap();
-        user_sessions.map.remove(&uuid);
+fn operation_result(conf: &config::LivyClient, msg: Option<String>) -> IronResult<Response> {
+    let result = OperationResult {
+        cluster: conf.name().to_string(),
+        msg,
+    };
+
+    match serde_json::to_string(&result) {
+        Ok(result) => Ok(response(status::Ok, &result, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
     }
+}
 
-    Ok(redirect(status::TemporaryRedirect, "/login", None))
+/// Items fetched from multiple Livy clusters
+#[derive(Serialize)]
+struct Aggregated<T> {
+    items: Vec<T>,
+    clusters: Vec<ClusterStatus>,
 }
 
-fn get_user_session(req: &mut Request) -> IronResult<Response> {
-    let user_session = req.extensions.get::<UserSessionBeforeMiddleware>();
+/// Result of fetching items from a Livy cluster
+#[derive(Serialize)]
+struct ClusterStatus {
+    name: String,
+    error: Option<String>,
+}
 
-    match serde_json::to_string(&user_session) {
-        Ok(user_session) => Ok(response(status::Ok, &user_session, application_json())),
-        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+/// Calls `f` for each of `clusters` concurrently and aggregates the results.
+/// A failure on a cluster is reported in the result instead of failing
+/// the whole request.
+fn fetch_from_clusters<T, F>(clusters: Vec<config::LivyClient>, f: F) -> Aggregated<T>
+    where T: Send + 'static,
+          F: Fn(&config::LivyClient) -> Result<Vec<T>, String> + Send + Sync + 'static {
+    let f = Arc::new(f);
+
+    let handles = clusters.into_iter().map(|cluster| {
+        let f = f.clone();
+        (cluster.name().to_string(), thread::spawn(move || f(&cluster)))
+    }).collect::<Vec<_>>();
+
+    let mut aggregated = Aggregated {
+        items: Vec::new(),
+        clusters: Vec::new(),
+    };
+
+    for (name, handle) in handles {
+        let result = match handle.join() {
+            Ok(result) => result,
+            Err(_) => Err("unexpected error".to_string()),
+        };
+
+        let error = match result {
+            Ok(mut items) => {
+                aggregated.items.append(&mut items);
+                None
+            },
+            Err(err) => {
+                eprintln!("failed to fetch from the Livy cluster {}: {}", name, err);
+                Some(err)
+            },
+        };
+
+        aggregated.clusters.push(ClusterStatus {
+            name,
+            error,
+        });
     }
+
+    aggregated
 }
 
-fn get_sessions(req: &mut Request) -> IronResult<Response> {
+fn has_session_authority(conf: &config::LivyClient, id: i64, action: Action, auth_required: bool, user_session: Option<&UserSession>) -> bool {
+    authz::is_allowed(auth_required, user_session, action, || {
+        match livy_ext::get_session(conf, id) {
+            Ok(session) => session.proxy_user,
+            Err(_) => None,
+        }
+    })
+}
+
+fn get_batches(req: &mut Request) -> IronResult<Response> {
     let auth_required =  auth_required(req);
     let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
         Some(user_session) => Some(user_session.clone()),
@@ -160,101 +1230,236 @@ fn get_sessions(req: &mut Request) -> IronResult<Response> {
         return Err(IronError::new(StringError(String::new()), status::Unauthorized));
     }
 
-    let client = livy_client(req);
+    let conf = route_cluster(req)?;
 
-    let sessions = match client.get_sessions(None, None) {
-        Ok(sessions) => sessions,
+    let batches = match list_batches(&conf, auth_required, user_session.as_ref()) {
+        Ok(batches) => batches,
         Err(err) => {
             return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError))
         },
     };
 
-    let mut sessions = match sessions.sessions {
-        Some(sessions) => sessions,
-        None => Vec::new(),
+    let batches = match serde_json::to_string(&batches) {
+        Ok(batches) => batches,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
     };
 
-    let (uid, is_admin) = match user_session {
-        Some(user_session) => (user_session.uid.clone(), user_session.is_admin),
-        None => (String::new(), false),
+    Ok(response(status::Ok, &batches, application_json()))
+}
+
+fn get_all_batches(req: &mut Request) -> IronResult<Response> {
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
     };
 
-    let sessions = sessions.iter_mut().filter(|ref session| {
-        if !auth_required || is_admin {
-            return true;
-        }
+    if auth_required && user_session.is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
 
-        match session.proxy_user {
-            Some(ref proxy_user) => proxy_user == uid.as_str(),
-            None => false,
-        }
-    }).map(|session| {
-        session.log = None;
-        session
-    }).collect::<Vec<_>>();
+    let clusters = query_clusters(req)?;
 
-    let sessions = match serde_json::to_string(&sessions) {
-        Ok(sessions) => sessions,
+    let batches = fetch_from_clusters(clusters, move |conf| {
+        list_batches(conf, auth_required, user_session.as_ref())
+    });
+
+    match serde_json::to_string(&batches) {
+        Ok(batches) => Ok(response(status::Ok, &batches, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// Returns the batches of the Livy cluster which can be seen by the user.
+fn list_batches(conf: &config::LivyClient, auth_required: bool, user_session: Option<&UserSession>) -> Result<Vec<livy_ext::Batch>, String> {
+    let batches = livy_ext::get_batches(conf, None, None)?;
+
+    Ok(batches.sessions.unwrap_or_default().into_iter().filter(|batch| {
+        authz::is_allowed(auth_required, user_session, Action::View, || batch.proxy_user.clone())
+    }).map(|mut batch| {
+        batch.cluster = Some(conf.name().to_string());
+        batch.log = None;
+        batch
+    }).collect())
+}
+
+fn get_batch(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+
+    let auth_required =  auth_required(req);
+    let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
+        Some(user_session) => Some(user_session.clone()),
+        None => None,
+    };
+
+    let conf = route_cluster(req)?;
+
+    let mut batch = match livy_ext::get_batch(&conf, id) {
+        Ok(batch) => batch,
         Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
     };
 
-    Ok(response(status::Ok, &sessions, application_json()))
-}
+    if !authz::is_allowed(auth_required, user_session.as_ref(), Action::View, || batch.proxy_user.clone()) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
+    }
 
-fn kill_session(req: &mut Request) -> IronResult<Response> {
-    let id = req.extensions.get::<Router>().unwrap()
-        .find("id").unwrap().to_string();
+    batch.cluster = Some(conf.name().to_string());
 
-    let id = match id.parse() {
-        Ok(id) => id,
+    let batch = match serde_json::to_string(&batch) {
+        Ok(batch) => batch,
         Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
     };
 
-    let client = livy_client(req);
+    Ok(response(status::Ok, &batch, application_json()))
+}
 
+fn create_batch(req: &mut Request) -> IronResult<Response> {
+    let auth_required =  auth_required(req);
     let user_session = match req.extensions.get::<UserSessionBeforeMiddleware>() {
         Some(user_session) => Some(user_session.clone()),
         None => None,
     };
 
-    if !has_kill_session_authority(&client, id, auth_required(req), user_session.as_ref()) {
+    if !authz::is_allowed(auth_required, user_session.as_ref(), Action::Create, || None) {
         return Err(IronError::new(StringError(String::new()), status::Unauthorized));
     }
 
-    match client.kill_session(id) {
-        Ok(_) => Ok(response(status::Ok, "{}", application_json())),
+    let form = read_json::<NewBatchForm>(req)?;
+
+    let templates = {
+        let arc = req.get::<Read<Config>>().unwrap();
+        arc.as_ref().batch_templates.clone().unwrap_or_default()
+    };
+
+    let request = match new_batch::build_request(form, &templates, user_session.as_ref()) {
+        Ok(request) => request,
+        Err(err) => return Err(json_error(status::BadRequest, err)),
+    };
+
+    let conf = route_cluster(req)?;
+
+    let mut batch = match livy_ext::create_batch(&conf, &request) {
+        Ok(batch) => batch,
         Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
-    }
+    };
+
+    batch.cluster = Some(conf.name().to_string());
+
+    let batch = match serde_json::to_string(&batch) {
+        Ok(batch) => batch,
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    };
+
+    Ok(response(status::Created, &batch, application_json()))
 }
 
-fn has_kill_session_authority(client: &Client, id: i64, auth_required: bool, user_session: Option<&UserSession>) -> bool {
-    if !auth_required {
-        return true;
+fn get_batch_templates(req: &mut Request) -> IronResult<Response> {
+    if auth_required(req) && req.extensions.get::<UserSessionBeforeMiddleware>().is_none() {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
     }
 
-    if user_session.is_none() {
-        return false;
+    let templates = {
+        let arc = req.get::<Read<Config>>().unwrap();
+        arc.as_ref().batch_templates.clone().unwrap_or_default()
+    };
+
+    match serde_json::to_string(&templates) {
+        Ok(templates) => Ok(response(status::Ok, &templates, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
     }
+}
+
+fn kill_batch(req: &mut Request) -> IronResult<Response> {
+    let id = route_id(req, "id")?;
+
+    let conf = route_cluster(req)?;
+    let client = livy_client(&conf);
+
+    // The batch is fetched once for the authorization, the audit log and the notification.
+    let batch = livy_ext::get_batch(&conf, id).ok();
+    let proxy_user = batch.as_ref().and_then(|batch| batch.proxy_user.clone());
+    let app_id = batch.and_then(|batch| batch.app_id);
+
+    kill(req, &conf, id, audit::Action::KillBatch, proxy_user, app_id, || {
+        client.kill_batch(id).map(|result| result.msg)
+    })
+}
 
-    let user_session = user_session.unwrap();
+/// Returns the audit events which match the query parameters `uid`,
+/// `action`, `since` and `until`, newest first.
+fn get_audit_events(req: &mut Request) -> IronResult<Response> {
+    let auth_required = auth_required(req);
+    let user_session = req.extensions.get::<UserSessionBeforeMiddleware>();
 
-    if user_session.is_admin {
-        return true;
+    if !authz::is_allowed(auth_required, user_session, Action::Administer, || None) {
+        return Err(IronError::new(StringError(String::new()), status::Unauthorized));
     }
 
-    match client.get_session(id) {
-        Ok(session) => {
-            match session.proxy_user {
-                Some(proxy_user) => {
-                    proxy_user == user_session.uid
-                },
-                None => false
-            }
+    let action = match query_string(req, "action")? {
+        Some(action) => match serde_json::from_value(serde_json::Value::String(action.clone())) {
+            Ok(action) => Some(action),
+            Err(_) => return Err(json_error(status::BadRequest, format!("unknown action: {}", action))),
         },
-        Err(_) => false,
+        None => None,
+    };
+
+    let filter = audit::Filter {
+        uid: query_string(req, "uid")?,
+        action,
+        since: query_i64(req, "since")?,
+        until: query_i64(req, "until")?,
+    };
+    let limit = query_i64(req, "limit")?.unwrap_or(100);
+
+    if limit <= 0 || limit > MAX_AUDIT_EVENTS {
+        return Err(json_error(status::BadRequest, format!("limit must be between 1 and {}", MAX_AUDIT_EVENTS)));
+    }
+
+    let arc = req.get::<Read<AuditLog>>().unwrap();
+    if !arc.as_ref().is_queryable() {
+        return Err(json_error(status::NotFound, "audit.path is not configured".to_string()));
+    }
+
+    let events = match arc.as_ref().query(&filter, limit as usize) {
+        Ok(events) => events,
+        Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+    };
+
+    match serde_json::to_string(&events) {
+        Ok(events) => Ok(response(status::Ok, &events, application_json())),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::InternalServerError)),
+    }
+}
+
+/// Creates an audit event of `action` by `uid` from the client of `req`.
+fn audit_event(req: &mut Request, uid: Option<String>, action: audit::Action, outcome: audit::Outcome) -> audit::Event {
+    audit::Event {
+        timestamp: time::get_time().sec,
+        uid,
+        client_ip: client_ip(req).to_string(),
+        action,
+        outcome,
+        cluster: None,
+        target_id: None,
+        proxy_user: None,
+        app_id: None,
+        detail: None,
+        reason: None,
     }
 }
 
+/// Writes `event` to the audit log.
+fn audit(req: &mut Request, event: &audit::Event) {
+    req.get::<Read<AuditLog>>().unwrap().as_ref().write(event);
+}
+
+/// Returns the address of the client, which is taken from `X-Forwarded-For`
+/// if the request is from one of `login_throttle.trusted_proxies`.
+fn client_ip(req: &mut Request) -> IpAddr {
+    let remote_addr = req.remote_addr.ip();
+    req.get::<Read<LoginThrottle>>().unwrap().client_ip(remote_addr, &req.headers)
+}
+
 fn text_html() -> Header<ContentType> {
     Header(ContentType(Mime(TopLevel::Text, SubLevel::Html, vec![(Attr::Charset, mime::Value::Utf8)])))
 }
@@ -280,22 +1485,35 @@ fn response(status_code: Status, body: &str, content_type: Header<ContentType>)
     Response::with((status_code, body, cache_control(), connection(), content_type))
 }
 
-fn redirect(status_code: Status, path: &str, uuid: Option<&str>) -> Response {
+/// Constructs a new `IronError` whose response has a JSON body
+/// which describes `msg`.
+fn json_error(status_code: Status, msg: String) -> IronError {
+    let body = serde_json::to_string(&ErrorMessage { error: msg.clone() }).unwrap_or_default();
+    IronError::new(StringError(msg), (status_code, body, cache_control(), application_json()))
+}
+
+/// Error message returned by the JSON API
+#[derive(Serialize)]
+struct ErrorMessage {
+    error: String,
+}
+
+fn redirect(status_code: Status, path: &str, cookie: Option<&SessionCookie>) -> Response {
     Response::with((
         status_code,
         cache_control(),
         connection(),
         text_html(),
         Header(Location(path.to_owned())),
-        set_cookie(uuid),
+        set_cookie(cookie),
     ))
 }
 
-fn set_cookie(uuid: Option<&str>) -> Header<SetCookie> {
-    match uuid {
-        Some(uuid) => {
-            let expires = time::now_utc() + Duration::days(7);
-            Header(SetCookie(vec![format!("{}={}; expires={}; path=/", COOKIE_NAME, uuid, expires.rfc822())]))
+fn set_cookie(cookie: Option<&SessionCookie>) -> Header<SetCookie> {
+    match cookie {
+        Some(cookie) => {
+            let expires = time::at_utc(time::Timespec::new(cookie.expires_at, 0));
+            Header(SetCookie(vec![format!("{}={}; expires={}; path=/", COOKIE_NAME, cookie.id, expires.rfc822())]))
         },
         None => {
             let expires = time::now_utc() - Duration::seconds(1);
@@ -304,22 +1522,156 @@ fn set_cookie(uuid: Option<&str>) -> Header<SetCookie> {
     }
 }
 
-fn livy_client(req: &mut Request) -> Client {
-    let arc = req.get::<Read<Config>>().unwrap();
-    let conf = arc.as_ref().livy_client.clone();
-
+fn livy_client(conf: &config::LivyClient) -> Client {
     Client::new(
         &conf.url,
         conf.gssnegotiate,
-        conf.username
+        conf.username.clone()
     )
 }
 
+/// Parses the route parameter `key` as an ID.
+fn route_id(req: &Request, key: &str) -> IronResult<i64> {
+    let id = req.extensions.get::<Router>().unwrap()
+        .find(key).unwrap_or("");
+
+    match id.parse() {
+        Ok(id) => Ok(id),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
+    }
+}
+
+/// Returns the optional non-empty query parameter `key`.
+fn query_string(req: &mut Request, key: &str) -> IronResult<Option<String>> {
+    let params = match req.get_ref::<Params>() {
+        Ok(params) => params.clone(),
+        Err(err) => return Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
+    };
+
+    match params.find(&[key]) {
+        Some(&params::Value::String(ref value)) if value.is_empty() => Ok(None),
+        Some(&params::Value::String(ref value)) => Ok(Some(value.clone())),
+        Some(_) => Err(IronError::new(StringError(format!("invalid parameter: {}", key)), status::BadRequest)),
+        None => Ok(None),
+    }
+}
+
+/// Parses the optional query parameter `key` as an integer.
+fn query_i64(req: &mut Request, key: &str) -> IronResult<Option<i64>> {
+    match query_string(req, key)? {
+        Some(value) => {
+            match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the Livy cluster specified by the query parameter `cluster`,
+/// or all of the Livy clusters if it is not specified.
+fn query_clusters(req: &mut Request) -> IronResult<Vec<config::LivyClient>> {
+    let name = query_string(req, "cluster")?;
+
+    let arc = req.get::<Read<Config>>().unwrap();
+
+    match name {
+        Some(name) => {
+            match arc.as_ref().cluster(&name) {
+                Some(cluster) => Ok(vec![cluster]),
+                None => Err(json_error(status::NotFound, format!("unknown cluster: {}", name))),
+            }
+        },
+        None => Ok(arc.as_ref().clusters()),
+    }
+}
+
+/// Deserializes the JSON request body.
+fn read_json<T: DeserializeOwned>(req: &mut Request) -> IronResult<T> {
+    let body = read_body(req)?;
+
+    match serde_json::from_str(&body) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(IronError::new(StringError(format!("{}", err)), status::BadRequest)),
+    }
+}
+
+/// Reads the request body, which has to be at most `MAX_JSON_BODY_BYTES`.
+fn read_body(req: &mut Request) -> IronResult<String> {
+    let mut body = Vec::new();
+
+    if let Err(err) = io::Read::read_to_end(&mut io::Read::take(&mut req.body, MAX_JSON_BODY_BYTES + 1), &mut body) {
+        return Err(IronError::new(StringError(format!("{}", err)), status::BadRequest));
+    }
+
+    if body.len() as u64 > MAX_JSON_BODY_BYTES {
+        return Err(json_error(status::PayloadTooLarge, format!("request body must be at most {} bytes", MAX_JSON_BODY_BYTES)));
+    }
+
+    String::from_utf8(body).map_err(|err| IronError::new(StringError(format!("{}", err)), status::BadRequest))
+}
+
+/// Returns the configuration of the Livy cluster specified by the route parameter `cluster`.
+fn route_cluster(req: &mut Request) -> IronResult<config::LivyClient> {
+    let name = req.extensions.get::<Router>().unwrap()
+        .find("cluster").unwrap_or("").to_string();
+
+    let arc = req.get::<Read<Config>>().unwrap();
+
+    match arc.as_ref().cluster(&name) {
+        Some(cluster) => Ok(cluster),
+        None => Err(json_error(status::NotFound, format!("unknown cluster: {}", name))),
+    }
+}
+
 /// User session
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct UserSession {
     pub uid: String,
-    pub is_admin: bool,
+    pub roles: Vec<Role>,
+    /// Proxy users of the Livy sessions and batches owned by the user,
+    /// in addition to the uid
+    #[serde(default)]
+    pub proxy_users: Vec<String>,
+    /// Teams which the user is a member of
+    #[serde(default)]
+    pub teams: Vec<Team>,
+}
+
+impl UserSession {
+    /// Returns true if the user has the admin role.
+    pub fn is_admin(&self) -> bool {
+        self.roles.contains(&Role::Admin)
+    }
+}
+
+/// User session returned by `/api/user_session`
+#[derive(Serialize)]
+struct UserSessionInfo<'a> {
+    uid: &'a str,
+    is_admin: bool,
+    roles: &'a [Role],
+    teams: Vec<&'a str>,
+    /// Whether the user can create API tokens
+    api_tokens: bool,
+    /// Token which must be sent as the `X-CSRF-Token` header with
+    /// state-changing API requests
+    csrf_token: &'a str,
+}
+
+/// Cookie which holds the ID of a login session
+#[derive(Clone, Debug)]
+pub struct SessionCookie {
+    pub id: String,
+    /// Unix time in seconds when the cookie expires
+    pub expires_at: i64,
+    /// Whether the login session was renewed by the current request
+    pub renewed: bool,
+}
+
+impl Key for SessionCookie {
+    type Value = Self;
 }
 
 pub struct UserSessionBeforeMiddleware;
@@ -330,37 +1682,206 @@ impl Key for UserSessionBeforeMiddleware {
 
 impl BeforeMiddleware for UserSessionBeforeMiddleware {
     fn before(&self, req: &mut Request) -> IronResult<()> {
-        if auth_required(req) {
-            match get_uuid(&req.headers) {
-                Some(uuid) => {
-                    let arc = req.get::<State<UserSessions>>().unwrap();
-                    let lock = arc.as_ref();
-                    let user_sessions = lock.read().unwrap();
-                    match user_sessions.map.get(&uuid) {
-                        Some(user_session) => {
-                            req.extensions.insert::<UserSessionBeforeMiddleware>(user_session.clone());
-                        },
-                        None => (),
-                    }
+        if !auth_required(req) {
+            return Ok(());
+        }
+
+        // The headers of the reverse proxy take precedence over login sessions.
+        let arc = req.get::<Read<ProxyAuthenticator>>().unwrap();
+        if let Some(ref authenticator) = *arc.as_ref() {
+            match authenticator.authenticate(&req.remote_addr.ip(), &req.headers) {
+                Ok(Some(user_session)) => {
+                    req.extensions.insert::<CsrfToken>(authenticator.csrf_token(&user_session.uid));
+                    req.extensions.insert::<UserSessionBeforeMiddleware>(user_session);
+                    return Ok(());
+                },
+                Ok(None) => (),
+                Err(err) => return Err(IronError::new(StringError(err), status::BadRequest)),
+            }
+        }
+
+        // API tokens are accepted only by the JSON API, whose requests with
+        // an invalid token are rejected rather than regarded as anonymous.
+        if let (Some(bearer), Some(&"api")) = (bearer_token(&req.headers), req.url.path().first()) {
+            let arc = req.get::<Read<ApiTokens>>().unwrap();
+            let store = match *arc.as_ref() {
+                Some(ref store) => store,
+                None => return Err(json_error(status::Unauthorized, "API tokens are not enabled".to_string())),
+            };
+
+            let token = match store.authenticate(&bearer) {
+                Ok(Some(token)) => token,
+                Ok(None) => return Err(json_error(status::Unauthorized, "invalid API token".to_string())),
+                Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+            };
+
+            if !token.scope.allows(req.method.as_ref()) {
+                return Err(json_error(status::Forbidden, "the API token is read-only".to_string()));
+            }
+
+            // Cross-site requests cannot send the Authorization header, so no
+            // CSRF token is required.
+            req.extensions.insert::<ApiTokenScope>(token.scope);
+            req.extensions.insert::<UserSessionBeforeMiddleware>(token.user_session);
+            return Ok(());
+        }
+
+        let uuid = match get_uuid(&req.headers) {
+            Some(uuid) => uuid,
+            None => return Ok(()),
+        };
+
+        let timeout = req.get::<Read<Config>>().unwrap().as_ref().session_timeout();
+        let arc = req.get::<Read<UserSessions>>().unwrap();
+        let store = arc.as_ref();
+
+        let mut entry = match store.get(&uuid) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(()),
+            Err(err) => return Err(IronError::new(StringError(err), status::InternalServerError)),
+        };
+
+        // Slides the idle expiry of the login session and its cookie.
+        let mut renewed = false;
+        if let Some(idle_secs) = timeout.idle_secs {
+            if let Some(idle_expires_at) = entry.renewed_idle_expiry(idle_secs, time::get_time().sec) {
+                if let Err(err) = store.renew(&uuid, idle_expires_at) {
+                    return Err(IronError::new(StringError(err), status::InternalServerError));
                 }
-                None => (),
+                entry.idle_expires_at = idle_expires_at;
+                renewed = true;
             }
         }
+
+        req.extensions.insert::<SessionCookie>(SessionCookie {
+            id: uuid,
+            expires_at: entry.valid_until(),
+            renewed,
+        });
+        req.extensions.insert::<CsrfToken>(entry.csrf_token);
+        req.extensions.insert::<UserSessionBeforeMiddleware>(entry.user_session);
+
         Ok(())
     }
 }
 
+/// Returns the token in the `Authorization: Bearer` header.
+fn bearer_token(headers: &Headers) -> Option<String> {
+    let value = headers.get_raw("Authorization")
+        .and_then(|values| values.first())
+        .and_then(|value| str::from_utf8(value).ok())?;
+
+    if value.len() <= BEARER.len() || !value[..BEARER.len()].eq_ignore_ascii_case(BEARER) {
+        return None;
+    }
+
+    Some(value[BEARER.len()..].trim().to_string())
+}
+
+/// Key of the scope of the API token with which the current request is made
+pub struct ApiTokenScope;
+
+impl Key for ApiTokenScope {
+    type Value = Scope;
+}
+
+/// Key of the CSRF token of the current login session
+pub struct CsrfToken;
+
+impl Key for CsrfToken {
+    type Value = String;
+}
+
+/// Rejects state-changing API requests of a login session which do not
+/// have its CSRF token.
+pub struct CsrfBeforeMiddleware;
+
+impl BeforeMiddleware for CsrfBeforeMiddleware {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        match req.method {
+            Method::Get | Method::Head | Method::Options => return Ok(()),
+            _ => (),
+        }
+
+        if req.url.path().first() != Some(&"api") {
+            return Ok(());
+        }
+
+        // Requests without a login session are rejected by the handlers.
+        let expected = match req.extensions.get::<CsrfToken>() {
+            Some(expected) => expected,
+            None => return Ok(()),
+        };
+
+        let token = req.headers.get_raw(CSRF_HEADER_NAME)
+            .and_then(|values| values.first())
+            .and_then(|value| String::from_utf8(value.clone()).ok());
+
+        match token {
+            Some(ref token) if secure_eq(token, expected) => Ok(()),
+            Some(_) => Err(json_error(status::Forbidden, "invalid CSRF token".to_string())),
+            None => Err(json_error(status::Forbidden, format!("{} header is required", CSRF_HEADER_NAME))),
+        }
+    }
+}
+
+/// Compares `a` and `b` in constant time with respect to their contents.
+fn secure_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub struct UserSessionAfterMiddleware;
+
+impl AfterMiddleware for UserSessionAfterMiddleware {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        if let Some(cookie) = req.extensions.get::<SessionCookie>() {
+            if cookie.renewed && !res.headers.has::<SetCookie>() {
+                res.headers.set(set_cookie(Some(cookie)).0);
+            }
+        }
+
+        // Appends the configured attributes to every cookie.
+        let attributes = req.get::<Read<Config>>().unwrap().as_ref().http.cookie().attributes();
+        if let Some(&mut SetCookie(ref mut cookies)) = res.headers.get_mut::<SetCookie>() {
+            for cookie in cookies.iter_mut() {
+                cookie.push_str(&attributes);
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Evicts expired login sessions from `store` every `interval_secs` seconds
+/// in a background thread.
+fn sweep_user_sessions(store: Arc<Box<session_store::Store>>, interval_secs: i64) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(::std::time::Duration::from_secs(interval_secs as u64));
+
+            if let Err(err) = store.remove_expired() {
+                eprintln!("failed to remove expired login sessions: {}", err);
+            }
+        }
+    });
+}
+
 fn auth_required(req: &mut Request) -> bool {
     let arc = req.get::<Read<Config>>().unwrap();
-    arc.as_ref().ldap.is_some()
+    arc.as_ref().auth_required()
 }
 
 pub fn get_uuid(headers: &Headers) -> Option<String> {
+    get_cookie(headers, COOKIE_NAME)
+}
+
+/// Returns the value of the cookie `name`.
+fn get_cookie(headers: &Headers, name: &str) -> Option<String> {
     for header in headers.iter() {
         if header.name() == "Cookie" {
             for kv in header.value_string().split(";") {
                 let kv = kv.trim().split("=").collect::<Vec<&str>>();
-                if kv.len() == 2 && kv[0] == COOKIE_NAME {
+                if kv.len() == 2 && kv[0] == name {
                     return Some(String::from(kv[1]));
                 }
             }
@@ -370,20 +1891,54 @@ pub fn get_uuid(headers: &Headers) -> Option<String> {
     None
 }
 
-pub struct UserSessions {
-    pub map: HashMap<String, UserSession>
+/// Key of the authenticator of users
+pub struct Authenticators;
+
+impl Key for Authenticators {
+    type Value = Box<Authenticator>;
 }
 
-impl UserSessions {
-    pub fn new() -> UserSessions {
-        UserSessions {
-            map: HashMap::new(),
-        }
-    }
+/// Key of the OpenID Connect client, which is `None` if it is not configured
+pub struct OidcClient;
+
+impl Key for OidcClient {
+    type Value = Option<oidc::Client>;
+}
+
+/// Key of the throttle of failed logins
+pub struct LoginThrottle;
+
+impl Key for LoginThrottle {
+    type Value = login_throttle::Throttle;
+}
+
+/// Key of the audit log
+pub struct AuditLog;
+
+impl Key for AuditLog {
+    type Value = audit::Log;
 }
 
+/// Key of the store of API tokens, which is `None` if it is not configured
+pub struct ApiTokens;
+
+impl Key for ApiTokens {
+    type Value = Option<api_token::Store>;
+}
+
+/// Key of the authenticator by the headers of a reverse proxy, which is
+/// `None` if it is not configured
+pub struct ProxyAuthenticator;
+
+impl Key for ProxyAuthenticator {
+    type Value = Option<proxy_auth::Authenticator>;
+}
+
+/// Key of the store of login sessions
+pub struct UserSessions;
+
 impl Key for UserSessions {
-    type Value = Self;
+    type Value = Box<session_store::Store>;
 }
 
 #[derive(Debug)]
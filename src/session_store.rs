@@ -0,0 +1,475 @@
+use config;
+use serde_json;
+use server::UserSession;
+use std::cmp;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use time;
+
+/// Idle expiry of a login session is extended only when it has advanced at
+/// least this many seconds, or half of the idle timeout if it is shorter,
+/// so that not every request writes to the store.
+const RENEWAL_INTERVAL_SECS: i64 = 60;
+
+/// Login session kept in a store
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub user_session: UserSession,
+    /// Token which must be sent with state-changing requests of the session
+    pub csrf_token: String,
+    /// Unix time in seconds after which the entry is invalid regardless
+    /// of the activity of the user
+    pub expires_at: i64,
+    /// Unix time in seconds after which the entry is invalid unless it is
+    /// renewed by the activity of the user
+    pub idle_expires_at: i64,
+}
+
+impl Entry {
+    /// Returns the Unix time in seconds after which the entry is invalid.
+    pub fn valid_until(&self) -> i64 {
+        cmp::min(self.expires_at, self.idle_expires_at)
+    }
+
+    /// Returns the idle expiry to which the entry is renewed by a request
+    /// at `now`, or `None` if it does not have to be renewed yet.
+    pub fn renewed_idle_expiry(&self, idle_secs: i64, now: i64) -> Option<i64> {
+        let idle_expires_at = now + idle_secs;
+        let interval = cmp::min(RENEWAL_INTERVAL_SECS, idle_secs / 2);
+
+        if idle_expires_at - self.idle_expires_at >= interval {
+            Some(idle_expires_at)
+        } else {
+            None
+        }
+    }
+
+    fn is_expired(&self, now: i64) -> bool {
+        self.valid_until() <= now
+    }
+}
+
+/// Store of login sessions keyed by the session ID held in the cookie
+pub trait Store: Send + Sync {
+    /// Returns the unexpired entry of `id`.
+    fn get(&self, id: &str) -> Result<Option<Entry>, String>;
+
+    /// Inserts `entry` as `id`. Returns `false` without modifying the store
+    /// if `id` already exists.
+    fn insert(&self, id: &str, entry: Entry) -> Result<bool, String>;
+
+    /// Extends the idle expiry of the entry of `id` up to `idle_expires_at`.
+    fn renew(&self, id: &str, idle_expires_at: i64) -> Result<(), String>;
+
+    /// Removes the entry of `id`.
+    fn remove(&self, id: &str) -> Result<(), String>;
+
+    /// Removes the expired entries and returns the number of them.
+    fn remove_expired(&self) -> Result<usize, String>;
+}
+
+/// Creates a new store from the configuration.
+pub fn new(conf: Option<&config::SessionStore>) -> Box<Store> {
+    match conf {
+        Some(&config::SessionStore::File { ref path }) => Box::new(FileStore::new(path)),
+        Some(&config::SessionStore::Memory) | None => Box::new(MemoryStore::new()),
+    }
+}
+
+fn now() -> i64 {
+    time::get_time().sec
+}
+
+/// Store which keeps login sessions in memory
+///
+/// All of the login sessions are lost when Livy Manager stops.
+pub struct MemoryStore {
+    map: RwLock<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    /// Creates a new `MemoryStore`.
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            map: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&self, id: &str) -> Result<Option<Entry>, String> {
+        let map = self.map.read().unwrap();
+
+        match map.get(id) {
+            Some(entry) if !entry.is_expired(now()) => Ok(Some(entry.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    fn insert(&self, id: &str, entry: Entry) -> Result<bool, String> {
+        let mut map = self.map.write().unwrap();
+
+        if map.contains_key(id) {
+            return Ok(false);
+        }
+
+        map.insert(id.to_string(), entry);
+        Ok(true)
+    }
+
+    fn renew(&self, id: &str, idle_expires_at: i64) -> Result<(), String> {
+        if let Some(entry) = self.map.write().unwrap().get_mut(id) {
+            entry.idle_expires_at = cmp::max(entry.idle_expires_at, idle_expires_at);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), String> {
+        self.map.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    fn remove_expired(&self) -> Result<usize, String> {
+        let mut map = self.map.write().unwrap();
+        let now = now();
+        let len = map.len();
+        map.retain(|_, entry| !entry.is_expired(now));
+        Ok(len - map.len())
+    }
+}
+
+/// Store which keeps login sessions in a JSON file
+///
+/// The file is replaced atomically by renaming a temporary file on every
+/// write and is re-read when it is modified, so that multiple Livy Manager
+/// instances can share it. Writes from those instances are serialized with
+/// a lock file.
+pub struct FileStore {
+    path: PathBuf,
+    cache: Mutex<Cache>,
+}
+
+struct Cache {
+    /// Modification time and inode number of the file which was read.
+    /// The inode number changes on every write because the file is replaced.
+    version: Option<(SystemTime, u64)>,
+    map: HashMap<String, Entry>,
+}
+
+/// Lock files older than this are regarded as left by a crashed process.
+const STALE_LOCK_SECS: u64 = 10;
+
+impl FileStore {
+    /// Creates a new `FileStore` which stores login sessions in `path`.
+    pub fn new(path: &str) -> FileStore {
+        FileStore {
+            path: PathBuf::from(path),
+            cache: Mutex::new(Cache {
+                version: None,
+                map: HashMap::new(),
+            }),
+        }
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        with_suffix(&self.path, ".lock")
+    }
+
+    /// Re-reads the file into `cache` if it was modified.
+    fn load(&self, cache: &mut Cache) -> Result<(), String> {
+        let version = match fs::metadata(&self.path) {
+            Ok(metadata) => Some(version(&metadata).map_err(|err| format!("{}", err))?),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        if version.is_some() && version == cache.version {
+            return Ok(());
+        }
+
+        cache.map = match version {
+            Some(_) => {
+                let mut contents = String::new();
+                File::open(&self.path)
+                    .and_then(|mut f| f.read_to_string(&mut contents))
+                    .map_err(|err| format!("{}", err))?;
+                let values: HashMap<String, serde_json::Value> = serde_json::from_str(&contents)
+                    .map_err(|err| format!("{}", err))?;
+
+                // Entries written by an older version of Livy Manager might not
+                // be parsed. They are dropped so that their users log in again.
+                values.into_iter().filter_map(|(id, value)| {
+                    serde_json::from_value(value).ok().map(|entry| (id, entry))
+                }).collect()
+            },
+            None => HashMap::new(),
+        };
+        cache.version = version;
+
+        Ok(())
+    }
+
+    /// Writes `cache` to a temporary file and renames it to the file.
+    fn save(&self, cache: &mut Cache) -> Result<(), String> {
+        let contents = serde_json::to_string(&cache.map).map_err(|err| format!("{}", err))?;
+        write_atomically(&self.path, contents.as_bytes()).map_err(|err| format!("{}", err))?;
+
+        cache.version = fs::metadata(&self.path)
+            .and_then(|metadata| version(&metadata))
+            .ok();
+
+        Ok(())
+    }
+
+    /// Re-reads the file, applies `f` to the login sessions and writes
+    /// them back while holding the lock file.
+    fn update<T, F>(&self, f: F) -> Result<T, String>
+        where F: FnOnce(&mut HashMap<String, Entry>) -> (T, bool) {
+        let mut cache = self.cache.lock().unwrap();
+        let _lock = LockFile::acquire(self.lock_path())?;
+
+        // Always re-read the file under the lock not to lose writes of other instances.
+        cache.version = None;
+        self.load(&mut cache)?;
+
+        let (result, modified) = f(&mut cache.map);
+
+        if modified {
+            self.save(&mut cache)?;
+        }
+
+        Ok(result)
+    }
+}
+
+impl Store for FileStore {
+    fn get(&self, id: &str) -> Result<Option<Entry>, String> {
+        let mut cache = self.cache.lock().unwrap();
+        self.load(&mut cache)?;
+
+        match cache.map.get(id) {
+            Some(entry) if !entry.is_expired(now()) => Ok(Some(entry.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    fn insert(&self, id: &str, entry: Entry) -> Result<bool, String> {
+        self.update(|map| {
+            if map.contains_key(id) {
+                return (false, false);
+            }
+
+            map.insert(id.to_string(), entry);
+            (true, true)
+        })
+    }
+
+    fn renew(&self, id: &str, idle_expires_at: i64) -> Result<(), String> {
+        self.update(|map| {
+            match map.get_mut(id) {
+                Some(ref mut entry) if entry.idle_expires_at < idle_expires_at => {
+                    entry.idle_expires_at = idle_expires_at;
+                    ((), true)
+                },
+                _ => ((), false),
+            }
+        })
+    }
+
+    fn remove(&self, id: &str) -> Result<(), String> {
+        self.update(|map| ((), map.remove(id).is_some()))
+    }
+
+    fn remove_expired(&self) -> Result<usize, String> {
+        self.update(|map| {
+            let now = now();
+            let len = map.len();
+            map.retain(|_, entry| !entry.is_expired(now));
+            let removed = len - map.len();
+            (removed, removed > 0)
+        })
+    }
+}
+
+fn version(metadata: &fs::Metadata) -> io::Result<(SystemTime, u64)> {
+    Ok((metadata.modified()?, metadata.ino()))
+}
+
+/// Returns `path` with `suffix` appended to its file name.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+/// Writes `contents` to a temporary file and renames it to `path`, which is
+/// readable only by the owner because it contains secrets such as session
+/// IDs. The directory is synced as well so that the rename survives a crash.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = with_suffix(path, ".tmp");
+
+    {
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+
+    fs::rename(&tmp_path, path)?;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Lock file which is removed when dropped
+pub struct LockFile {
+    path: PathBuf,
+    /// Version of the created file, so that a lock which has been taken
+    /// over as stale by another process is not removed
+    version: Option<(SystemTime, u64)>,
+}
+
+impl LockFile {
+    /// Creates the lock file on `path`, waiting while another process holds it.
+    pub fn acquire(path: PathBuf) -> Result<LockFile, String> {
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(f) => {
+                    let version = f.metadata().and_then(|metadata| version(&metadata)).ok();
+                    return Ok(LockFile { path, version });
+                },
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+                    let metadata = match fs::metadata(&path) {
+                        Ok(metadata) => metadata,
+                        // The lock has just been released.
+                        Err(_) => continue,
+                    };
+
+                    let stale = metadata.modified().ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .map(|elapsed| elapsed.as_secs() >= STALE_LOCK_SECS)
+                        .unwrap_or(false);
+
+                    // The lock is removed only if it is still the stale one,
+                    // not one which another process has created after removing it.
+                    if stale && lock_version(&path) == version(&metadata).ok() {
+                        let _ = fs::remove_file(&path);
+                    } else {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                },
+                Err(err) => return Err(format!("{}", err)),
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.version.is_some() && lock_version(&self.path) == self.version {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn lock_version(path: &Path) -> Option<(SystemTime, u64)> {
+    fs::metadata(path).and_then(|metadata| version(&metadata)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use authz::Role;
+    use std::env;
+    use std::process;
+
+    fn entry(uid: &str, expires_at: i64) -> Entry {
+        Entry {
+            user_session: UserSession {
+                uid: uid.to_string(),
+                roles: vec![Role::User],
+                proxy_users: Vec::new(),
+                teams: Vec::new(),
+            },
+            csrf_token: uid.to_string(),
+            expires_at,
+            idle_expires_at: expires_at,
+        }
+    }
+
+    #[test]
+    fn test_file_store() {
+        let path = env::temp_dir().join(format!("livy-manager-sessions-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let store = FileStore::new(path);
+        let t = now();
+        assert_eq!(Ok(true), store.insert("a", entry("alice", t + 60)));
+        assert_eq!(Ok(false), store.insert("a", entry("bob", t + 60)));
+        assert_eq!(Ok(true), store.insert("b", entry("bob", t - 1)));
+
+        // Another instance sharing the file sees the same sessions.
+        let other = FileStore::new(path);
+        assert_eq!("alice", other.get("a").unwrap().unwrap().user_session.uid);
+        assert!(other.get("b").unwrap().is_none());
+        assert_eq!(Ok(1), other.remove_expired());
+
+        other.renew("a", t + 120).unwrap();
+        assert_eq!(t + 60, store.get("a").unwrap().unwrap().valid_until());
+
+        store.remove("a").unwrap();
+        assert!(other.get("a").unwrap().is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_lock_file() {
+        let path = env::temp_dir().join(format!("livy-manager-sessions-{}.lock", process::id()));
+        let _ = fs::remove_file(&path);
+
+        // A stale lock left by a crashed process is taken over.
+        File::create(&path).unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(STALE_LOCK_SECS + 1)).unwrap();
+        let lock = LockFile::acquire(path.clone()).unwrap();
+
+        // The lock of another process which has taken it over is kept.
+        File::create(with_suffix(&path, ".new")).unwrap();
+        fs::rename(with_suffix(&path, ".new"), &path).unwrap();
+        drop(lock);
+        assert!(path.exists());
+
+        fs::remove_file(&path).unwrap();
+        drop(LockFile::acquire(path.clone()).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_renewed_idle_expiry() {
+        // Renewed at most every 60 seconds
+        let e = entry("alice", 1000);
+        assert_eq!(None, e.renewed_idle_expiry(3600, 1000 - 3600 + 59));
+        assert_eq!(Some(1060), e.renewed_idle_expiry(3600, 1000 - 3600 + 60));
+
+        // Renewed every half of an idle timeout shorter than 2 minutes,
+        // before it expires
+        assert_eq!(None, e.renewed_idle_expiry(30, 1000 - 30 + 14));
+        assert_eq!(Some(1015), e.renewed_idle_expiry(30, 1000 - 30 + 15));
+        assert_eq!(Some(1029), e.renewed_idle_expiry(30, 999));
+    }
+}
@@ -0,0 +1,254 @@
+use authz;
+use config::Config;
+use server::UserSession;
+
+/// Authenticates the user of the SPNEGO `token` sent by a browser and returns
+/// the login session and the token to be sent back to the browser.
+pub fn authenticate(conf: &Config, token: &[u8]) -> Result<(UserSession, Vec<u8>), String> {
+    let spnego = conf.spnego.as_ref().ok_or_else(|| "spnego is not configured".to_string())?;
+
+    let (principal, output) = accept(token)?;
+    let uid = uid(&principal, &spnego.realms)
+        .ok_or_else(|| format!("principal {} is not a user of {:?}", principal, spnego.realms))?;
+
+    let proxy_user_aliases = conf.proxy_user_aliases();
+
+    Ok((UserSession {
+        roles: spnego.roles(&uid),
+        proxy_users: authz::proxy_users(&proxy_user_aliases, &uid),
+        teams: authz::static_teams(&conf.teams(), &proxy_user_aliases, &uid),
+        uid,
+    }, output))
+}
+
+/// Returns the uid of the Kerberos `principal`, which is the principal
+/// without its realm if the realm is one of `realms`.
+///
+/// Principals with an instance such as `HTTP/host@REALM` are not users
+/// and are rejected.
+pub fn uid(principal: &str, realms: &[String]) -> Option<String> {
+    let at = principal.rfind('@')?;
+    let (name, realm) = (&principal[..at], &principal[at + 1..]);
+
+    if name.is_empty() || name.contains('/') || !realms.iter().any(|r| r == realm) {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+#[cfg(feature = "spnego")]
+pub use self::gss::{accept, register_keytab};
+
+#[cfg(not(feature = "spnego"))]
+pub use self::disabled::{accept, register_keytab};
+
+/// Acceptor of SPNEGO tokens with the GSS-API of MIT Kerberos
+#[cfg(feature = "spnego")]
+mod gss {
+    use libc::{c_char, c_int, c_void, size_t};
+    use std::ffi::CString;
+    use std::ptr;
+    use std::slice;
+
+    type OmUint32 = u32;
+
+    #[repr(C)]
+    struct GssBufferDesc {
+        length: size_t,
+        value: *mut c_void,
+    }
+
+    const GSS_S_COMPLETE: OmUint32 = 0;
+    const GSS_S_CONTINUE_NEEDED: OmUint32 = 1;
+    const GSS_C_GSS_CODE: c_int = 1;
+    const GSS_C_MECH_CODE: c_int = 2;
+
+    #[link(name = "gssapi_krb5")]
+    extern "C" {
+        fn krb5_gss_register_acceptor_identity(keytab: *const c_char) -> OmUint32;
+
+        fn gss_accept_sec_context(
+            minor_status: *mut OmUint32,
+            context_handle: *mut *mut c_void,
+            acceptor_cred_handle: *mut c_void,
+            input_token_buffer: *mut GssBufferDesc,
+            input_chan_bindings: *mut c_void,
+            src_name: *mut *mut c_void,
+            mech_type: *mut *mut c_void,
+            output_token: *mut GssBufferDesc,
+            ret_flags: *mut OmUint32,
+            time_rec: *mut OmUint32,
+            delegated_cred_handle: *mut *mut c_void,
+        ) -> OmUint32;
+
+        fn gss_display_name(
+            minor_status: *mut OmUint32,
+            input_name: *mut c_void,
+            output_name_buffer: *mut GssBufferDesc,
+            output_name_type: *mut *mut c_void,
+        ) -> OmUint32;
+
+        fn gss_display_status(
+            minor_status: *mut OmUint32,
+            status_value: OmUint32,
+            status_type: c_int,
+            mech_type: *mut c_void,
+            message_context: *mut OmUint32,
+            status_string: *mut GssBufferDesc,
+        ) -> OmUint32;
+
+        fn gss_release_buffer(minor_status: *mut OmUint32, buffer: *mut GssBufferDesc) -> OmUint32;
+
+        fn gss_release_name(minor_status: *mut OmUint32, name: *mut *mut c_void) -> OmUint32;
+
+        fn gss_delete_sec_context(
+            minor_status: *mut OmUint32,
+            context_handle: *mut *mut c_void,
+            output_token: *mut GssBufferDesc,
+        ) -> OmUint32;
+    }
+
+    impl GssBufferDesc {
+        fn empty() -> GssBufferDesc {
+            GssBufferDesc {
+                length: 0,
+                value: ptr::null_mut(),
+            }
+        }
+
+        /// Copies the buffer allocated by the GSS-API and releases it.
+        unsafe fn take(&mut self) -> Vec<u8> {
+            let bytes = if self.value.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(self.value as *const u8, self.length).to_vec()
+            };
+            let mut minor = 0;
+            gss_release_buffer(&mut minor, self);
+            bytes
+        }
+    }
+
+    /// Makes the keytab used to accept the tokens of browsers.
+    pub fn register_keytab(keytab: &str) -> Result<(), String> {
+        let keytab = CString::new(keytab).map_err(|err| format!("{}", err))?;
+
+        match unsafe { krb5_gss_register_acceptor_identity(keytab.as_ptr()) } {
+            GSS_S_COMPLETE => Ok(()),
+            major => Err(format!("failed to register the keytab: {}", status_message(major, 0))),
+        }
+    }
+
+    /// Accepts the SPNEGO `token` of a browser and returns the principal of
+    /// the user and the token to be sent back to the browser.
+    ///
+    /// Only the tokens which complete the authentication in a single round
+    /// trip, such as the ones for Kerberos, are accepted.
+    pub fn accept(token: &[u8]) -> Result<(String, Vec<u8>), String> {
+        let mut minor = 0;
+        let mut context = ptr::null_mut();
+        let mut input = GssBufferDesc {
+            length: token.len(),
+            value: token.as_ptr() as *mut c_void,
+        };
+        let mut src_name = ptr::null_mut();
+        let mut output = GssBufferDesc::empty();
+
+        let major = unsafe {
+            gss_accept_sec_context(&mut minor, &mut context, ptr::null_mut(), &mut input, ptr::null_mut(),
+                                   &mut src_name, ptr::null_mut(), &mut output, ptr::null_mut(),
+                                   ptr::null_mut(), ptr::null_mut())
+        };
+
+        let output = unsafe { output.take() };
+
+        let result = match major {
+            GSS_S_COMPLETE => display_name(src_name).map(|principal| (principal, output)),
+            GSS_S_CONTINUE_NEEDED => Err("SPNEGO tokens which need more round trips are not supported".to_string()),
+            _ => Err(status_message(major, minor)),
+        };
+
+        unsafe {
+            if !src_name.is_null() {
+                gss_release_name(&mut minor, &mut src_name);
+            }
+            if !context.is_null() {
+                gss_delete_sec_context(&mut minor, &mut context, ptr::null_mut());
+            }
+        }
+
+        result
+    }
+
+    fn display_name(name: *mut c_void) -> Result<String, String> {
+        let mut minor = 0;
+        let mut buffer = GssBufferDesc::empty();
+
+        let major = unsafe { gss_display_name(&mut minor, name, &mut buffer, ptr::null_mut()) };
+        if major != GSS_S_COMPLETE {
+            return Err(status_message(major, minor));
+        }
+
+        String::from_utf8(unsafe { buffer.take() }).map_err(|err| format!("{}", err))
+    }
+
+    /// Returns the messages of the major and the minor status codes.
+    fn status_message(major: OmUint32, minor: OmUint32) -> String {
+        let mut messages = Vec::new();
+
+        for &(status, status_type) in [(major, GSS_C_GSS_CODE), (minor, GSS_C_MECH_CODE)].iter() {
+            if status == 0 {
+                continue;
+            }
+
+            let mut message_context = 0;
+            loop {
+                let mut minor = 0;
+                let mut buffer = GssBufferDesc::empty();
+                let major = unsafe {
+                    gss_display_status(&mut minor, status, status_type, ptr::null_mut(), &mut message_context, &mut buffer)
+                };
+                if major != GSS_S_COMPLETE {
+                    break;
+                }
+                messages.push(String::from_utf8_lossy(&unsafe { buffer.take() }).into_owned());
+                if message_context == 0 {
+                    break;
+                }
+            }
+        }
+
+        messages.join(": ")
+    }
+}
+
+/// Stubs used when Livy Manager is built without the `spnego` feature, in
+/// which case the configuration with `spnego` is rejected on startup
+#[cfg(not(feature = "spnego"))]
+mod disabled {
+    pub fn register_keytab(_keytab: &str) -> Result<(), String> {
+        Err("built without the spnego feature".to_string())
+    }
+
+    pub fn accept(_token: &[u8]) -> Result<(String, Vec<u8>), String> {
+        Err("built without the spnego feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uid() {
+        let realms = vec!["EXAMPLE.COM".to_string()];
+
+        assert_eq!(Some("alice".to_string()), uid("alice@EXAMPLE.COM", &realms));
+        assert_eq!(None, uid("alice@OTHER.COM", &realms));
+        assert_eq!(None, uid("alice@example.com", &realms));
+        assert_eq!(None, uid("HTTP/host.example.com@EXAMPLE.COM", &realms));
+        assert_eq!(None, uid("@EXAMPLE.COM", &realms));
+        assert_eq!(None, uid("alice", &realms));
+    }
+}
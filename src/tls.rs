@@ -0,0 +1,236 @@
+use config;
+use hyper;
+use hyper::net::{HttpStream, NetworkStream, SslServer};
+use libc;
+use openssl::ssl::{self, HandshakeError, SslAcceptor, SslAcceptorBuilder, SslMethod, SslStream};
+use openssl::x509::{X509Name, X509_FILETYPE_PEM};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Timeout of reads and writes during a TLS handshake, which keeps clients
+/// that never send a ClientHello from blocking the acceptor
+const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+/// Server which wraps accepted connections with TLS
+///
+/// The certificate can be replaced by `reload` while the server is running.
+/// Connections accepted after that use the new certificate.
+#[derive(Clone)]
+pub struct TlsServer {
+    conf: config::TLS,
+    acceptor: Arc<RwLock<SslAcceptor>>,
+}
+
+impl TlsServer {
+    /// Creates a new `TlsServer` from the files specified by `conf`.
+    pub fn new(conf: &config::TLS) -> Result<TlsServer, String> {
+        Ok(TlsServer {
+            conf: conf.clone(),
+            acceptor: Arc::new(RwLock::new(build_acceptor(conf)?)),
+        })
+    }
+
+    /// Re-reads the certificate files. The current certificate is kept
+    /// if they are invalid.
+    pub fn reload(&self) -> Result<(), String> {
+        let acceptor = build_acceptor(&self.conf)?;
+        *self.acceptor.write().unwrap() = acceptor;
+        Ok(())
+    }
+}
+
+impl SslServer for TlsServer {
+    type Stream = TlsStream;
+
+    fn wrap_server(&self, stream: HttpStream) -> hyper::Result<TlsStream> {
+        let acceptor = self.acceptor.read().unwrap().clone();
+        let timeout = Some(Duration::from_secs(HANDSHAKE_TIMEOUT_SECS));
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        match acceptor.accept(stream) {
+            Ok(stream) => {
+                // hyper sets its own timeouts of the connection afterwards.
+                stream.get_ref().set_read_timeout(None)?;
+                stream.get_ref().set_write_timeout(None)?;
+                Ok(TlsStream(Arc::new(Mutex::new(stream))))
+            },
+            Err(HandshakeError::SetupFailure(err)) => Err(hyper::Error::Ssl(Box::new(err))),
+            Err(HandshakeError::Failure(stream)) |
+            Err(HandshakeError::Interrupted(stream)) => Err(hyper::Error::Ssl(Box::new(stream.into_error()))),
+        }
+    }
+}
+
+fn build_acceptor(conf: &config::TLS) -> Result<SslAcceptor, String> {
+    let mut builder = SslAcceptorBuilder::mozilla_intermediate_raw(SslMethod::tls())
+        .map_err(|err| format!("{}", err))?;
+
+    builder.set_certificate_chain_file(&conf.cert_chain_file)
+        .map_err(|err| format!("{}: {}", conf.cert_chain_file, err))?;
+    builder.set_private_key_file(&conf.private_key_file, X509_FILETYPE_PEM)
+        .map_err(|err| format!("{}: {}", conf.private_key_file, err))?;
+    builder.check_private_key()
+        .map_err(|err| format!("the private key does not match the certificate: {}", err))?;
+
+    if let Some(ref client_ca_file) = conf.client_ca_file {
+        builder.set_ca_file(client_ca_file)
+            .map_err(|err| format!("{}: {}", client_ca_file, err))?;
+        builder.set_client_ca_list(X509Name::load_client_ca_file(client_ca_file)
+            .map_err(|err| format!("{}: {}", client_ca_file, err))?);
+        builder.set_verify(ssl::SSL_VERIFY_PEER | ssl::SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+/// TLS stream of a connection
+///
+/// hyper reads requests from and writes responses to clones of a stream
+/// one after the other, so they share the TLS session.
+#[derive(Clone)]
+pub struct TlsStream(Arc<Mutex<SslStream<HttpStream>>>);
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+impl NetworkStream for TlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.0.lock().unwrap().get_mut().peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.0.lock().unwrap().get_mut().close(how)
+    }
+}
+
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_sighup(_: libc::c_int) {
+    SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+/// Reloads the certificate of `server` when the process receives SIGHUP.
+pub fn reload_on_sighup(server: TlsServer) {
+    unsafe {
+        libc::signal(libc::SIGHUP, handle_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_secs(1));
+
+            if SIGHUP_RECEIVED.swap(false, Ordering::SeqCst) {
+                match server.reload() {
+                    Ok(()) => eprintln!("Reloaded the TLS certificate."),
+                    Err(err) => eprintln!("failed to reload the TLS certificate: {}", err),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{Ssl, SslContext};
+    use openssl::x509::{X509, X509NameBuilder};
+    use std::env;
+    use std::fs::{self, File};
+    use std::net::{TcpListener, TcpStream};
+    use std::process;
+
+    /// Writes a self-signed certificate whose CN is `cn` and its private key.
+    fn write_certificate(cn: &str, conf: &config::TLS) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(nid::COMMONNAME, cn).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        File::create(&conf.cert_chain_file).unwrap().write_all(&builder.build().to_pem().unwrap()).unwrap();
+        File::create(&conf.private_key_file).unwrap().write_all(&key.private_key_to_pem().unwrap()).unwrap();
+    }
+
+    /// Returns the CN of the certificate which `server` presents to a client.
+    fn served_cn(server: &TlsServer) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let ctx = SslContext::builder(SslMethod::tls()).unwrap().build();
+            let stream = Ssl::new(&ctx).unwrap().connect(TcpStream::connect(addr).unwrap()).unwrap();
+            let cert = stream.ssl().peer_certificate().unwrap();
+            let cn = cert.subject_name().entries_by_nid(nid::COMMONNAME).next().unwrap().data().as_slice().to_vec();
+            String::from_utf8(cn).unwrap()
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        assert!(server.wrap_server(HttpStream(stream)).is_ok());
+        client.join().unwrap()
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = env::temp_dir();
+        let conf = config::TLS {
+            cert_chain_file: dir.join(format!("livy-manager-cert-{}.pem", process::id())).to_str().unwrap().to_string(),
+            private_key_file: dir.join(format!("livy-manager-key-{}.pem", process::id())).to_str().unwrap().to_string(),
+            client_ca_file: None,
+        };
+
+        write_certificate("old.example.com", &conf);
+        let server = TlsServer::new(&conf).unwrap();
+        assert_eq!("old.example.com", served_cn(&server));
+
+        // The current certificate is kept if the new files are invalid.
+        File::create(&conf.cert_chain_file).unwrap().write_all(b"invalid").unwrap();
+        assert!(server.reload().is_err());
+        assert_eq!("old.example.com", served_cn(&server));
+
+        write_certificate("new.example.com", &conf);
+        assert!(server.reload().is_ok());
+        assert_eq!("new.example.com", served_cn(&server));
+
+        fs::remove_file(&conf.cert_chain_file).unwrap();
+        fs::remove_file(&conf.private_key_file).unwrap();
+    }
+}